@@ -0,0 +1,142 @@
+// Every bench that includes this module uses only some of the helpers.
+#![allow(dead_code)]
+
+use uniq::{BinaryOperator, Frame, Instruction, Program, RuntimeErrorKind, VMResult, Value};
+
+pub const STACK_SIZE: usize = 256;
+
+// Every level calls the one below twice, so a run makes 2^depth leaf calls.
+pub fn call_tree(depth: usize, leaf: &str) -> String {
+    let mut source = format!("fn f0(x) {{ {leaf} }}\n");
+    for level in 1..=depth {
+        let below = level - 1;
+        source.push_str(&format!(
+            "fn f{level}(x) {{\n  f{below}(x + 1) + f{below}(x * 2)\n}}\n"
+        ));
+    }
+    source.push_str(&format!("f{depth}(1)\n"));
+    source
+}
+
+pub type Operation = fn(Value, Value) -> VMResult<Value>;
+
+pub fn operation(op: BinaryOperator) -> Operation {
+    match op {
+        BinaryOperator::Addict => Value::addict,
+        BinaryOperator::Subtract => Value::subtract,
+        BinaryOperator::Multiply => Value::multiply,
+        BinaryOperator::Divide => Value::divide,
+        BinaryOperator::Modulo => Value::modulo,
+        BinaryOperator::Equals => Value::equals,
+        BinaryOperator::NotEquals => Value::not_equals,
+        BinaryOperator::Less => Value::less,
+        BinaryOperator::Greater => Value::greater,
+        BinaryOperator::LessEquals => Value::less_equals,
+        BinaryOperator::GreaterEquals => Value::greater_equals,
+    }
+}
+
+// The register VM loop with plain `Value` enums in its registers instead of packed words.
+// It keeps the same bounds and error checks as `State` so only the representation differs.
+pub struct EnumVm {
+    stack: [Value; STACK_SIZE],
+    base: usize,
+    program_counter: usize,
+    frames: Vec<Frame>,
+}
+
+impl EnumVm {
+    pub fn new() -> Self {
+        Self {
+            stack: [Value::Void; STACK_SIZE],
+            base: 0,
+            program_counter: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    fn register(&self, register: u16) -> VMResult<Value> {
+        match self.stack.get(self.base + register as usize) {
+            Some(value) => Ok(*value),
+            None => Err(Box::new(RuntimeErrorKind::StackOverflow)),
+        }
+    }
+
+    fn advance(&mut self, dst: u16, value: Value) -> VMResult<bool> {
+        let index = self.base + dst as usize;
+        if index >= STACK_SIZE {
+            return Err(Box::new(RuntimeErrorKind::StackOverflow));
+        }
+        self.stack[index] = value;
+        self.program_counter += 1;
+        Ok(true)
+    }
+
+    fn call(&mut self, program: &Program, function: u32, base: u16) -> VMResult<bool> {
+        let Some(info) = program.function(function as usize) else {
+            return Err(Box::new(RuntimeErrorKind::InvalidFunction(function)));
+        };
+        if self.frames.len() >= STACK_SIZE {
+            return Err(Box::new(RuntimeErrorKind::StackOverflow));
+        }
+        let base = self.base + base as usize;
+        if base + info.arity as usize > STACK_SIZE {
+            return Err(Box::new(RuntimeErrorKind::StackOverflow));
+        }
+        self.frames.push(Frame {
+            return_address: self.program_counter + 1,
+            base,
+            function: function as usize,
+        });
+        self.base = base;
+        self.program_counter = info.entry;
+        Ok(true)
+    }
+
+    fn ret(&mut self, src: u16) -> VMResult<bool> {
+        let result = self.register(src)?;
+        let Some(frame) = self.frames.pop() else {
+            return Err(Box::new(RuntimeErrorKind::StackUnderflow));
+        };
+        self.stack[frame.base] = result;
+        self.base = self.frames.last().map_or(0, |frame| frame.base);
+        self.program_counter = frame.return_address;
+        Ok(true)
+    }
+
+    fn step(&mut self, program: &Program) -> VMResult<bool> {
+        let Some(instruction) = program.instruction(self.program_counter) else {
+            return Err(Box::new(RuntimeErrorKind::ProgramCounterOutOfBounds));
+        };
+        match instruction {
+            Instruction::Integer(dst, value) => self.advance(dst, Value::Integer(value)),
+            Instruction::Float(dst, value) => self.advance(dst, Value::Float(value)),
+            Instruction::Boolean(dst, value) => self.advance(dst, Value::Boolean(value)),
+            Instruction::Void(dst) => self.advance(dst, Value::Void),
+            Instruction::Move(dst, src) => self.advance(dst, self.register(src)?),
+            Instruction::Binary(op, dst, left, right) => {
+                let result = operation(op)(self.register(left)?, self.register(right)?)?;
+                self.advance(dst, result)
+            }
+            Instruction::BinaryConst(op, dst, left, right) => {
+                let result = operation(op)(self.register(left)?, Value::Integer(right))?;
+                self.advance(dst, result)
+            }
+            Instruction::Call(function, base) => self.call(program, function, base),
+            Instruction::Return(src) => self.ret(src),
+            Instruction::End(_) => Ok(false),
+            instruction => panic!("unsupported instruction {instruction}"),
+        }
+    }
+
+    pub fn run(&mut self, program: &Program) -> VMResult<Value> {
+        self.program_counter = program.entry();
+        self.base = 0;
+        self.frames.clear();
+        while self.step(program)? {}
+        match program.instruction(self.program_counter) {
+            Some(Instruction::End(src)) => self.register(src),
+            _ => Err(Box::new(RuntimeErrorKind::ProgramCounterOutOfBounds)),
+        }
+    }
+}
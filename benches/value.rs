@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::{call_tree, EnumVm};
+use uniq::{Program, State, Value};
+
+const RUNS: usize = 10;
+const DEPTH: usize = 14;
+
+fn workload(leaf: &str) -> Program {
+    uniq::parse_and_compile(call_tree(DEPTH, leaf).as_bytes()).unwrap()
+}
+
+fn measure<F: FnMut() -> Value>(mut f: F) -> (Duration, Value) {
+    let mut result = Value::Void;
+    let time = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            result = f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    (time, result)
+}
+
+fn main() {
+    println!(
+        "enum Value is {} bytes, the VM registers store 8-byte words",
+        std::mem::size_of::<Value>()
+    );
+    let workloads = [
+        ("integer", "x * 3 + x % 7 - x / 2"),
+        ("float", "x * 1.5 + x / 2.5 - x % 0.75"),
+        ("mixed", "x * 0.5 + x - 1 + x % 3"),
+        ("big integer", "x * 4611686018427387904 + 9007199254740993"),
+    ];
+    for (name, leaf) in workloads {
+        let program = workload(leaf);
+        let mut state = State::new();
+        let (packed, packed_result) = measure(|| state.run(&program).unwrap());
+        let mut vm = EnumVm::new();
+        let (plain, plain_result) = measure(|| vm.run(&program).unwrap());
+        assert_eq!(packed_result, plain_result, "{name}");
+        println!(
+            "{name:>12}: words {packed:>10.3?}, enum {plain:>10.3?}, speed-up {:.2}x",
+            plain.as_secs_f64() / packed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::call_tree;
+use uniq::{CompileOptions, Program};
+
+const DEPTH: usize = 16;
+const RUNS: usize = 10;
+
+fn compile(source: &str, level: u8) -> Program {
+    let ast = uniq::parse(source.as_bytes()).unwrap();
+    uniq::compile_with_options(&ast, CompileOptions::new().with_optimization(level)).unwrap()
+}
+
+fn measure(program: &Program) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            uniq::run(program).unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let source = call_tree(DEPTH, "x * 3 + 1 - x % 7 / 2");
+    let mut baseline = None;
+    for level in 0..=CompileOptions::MAX_OPTIMIZATION {
+        let program = compile(&source, level);
+        let time = measure(&program);
+        let baseline = *baseline.get_or_insert(time);
+        println!(
+            "call tree -O{level}: {:>5} instructions, {:>10.3?}, speed-up {:.2}x",
+            program.instructions().len(),
+            time,
+            baseline.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
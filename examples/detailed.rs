@@ -1,43 +1,16 @@
-fn build(code: &str) -> uniq::Program {
-    let ast = match uniq::parse(code.as_bytes()) {
-        Ok(ast) => ast,
-        Err(error) => {
-            uniq::print_error(
-                format!("Parsing error: {}", error.message),
-                error.location,
-                "user code",
-                code,
-            );
-            std::process::exit(1);
-        }
-    };
-
-    match uniq::compile(&ast) {
-        Ok(program) => program,
-        Err(error) => {
-            uniq::print_error(
-                format!("Compilation error: {}", error.message),
-                error.location,
-                "user code",
-                code,
-            );
-            std::process::exit(1);
-        }
-    }
-}
-
 fn eval(code: &str) -> uniq::Value {
-    let program = build(code);
+    let mut sources = uniq::SourceMap::new();
+    let file = sources.add("user code".to_string(), code.to_string());
+    let result = uniq::parse_file(&sources, file)
+        .and_then(|ast| uniq::compile(&ast))
+        .and_then(|program| uniq::run(&program));
 
-    match uniq::run(&program) {
+    match result {
         Ok(result) => result,
         Err(error) => {
-            uniq::print_error(
-                format!("Compilation error: {}", error.message),
-                error.location,
-                "user code",
-                code,
-            );
+            let renderer = uniq::Renderer::new();
+            let diagnostic = error.diagnostic();
+            let _ = renderer.render(&mut std::io::stdout(), &diagnostic, &sources);
             std::process::exit(1);
         }
     }
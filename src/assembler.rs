@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use crate::{
+    token::TokenLocation, BinaryOperator, Error, FileId, FunctionInfo, Instruction, LineTable,
+    Program, Register, Result, SourceError,
+};
+
+struct Line<'a> {
+    text: &'a str,
+    location: TokenLocation,
+}
+
+impl Line<'_> {
+    fn error<T>(&self, message: String) -> Result<T> {
+        Err(Error::Assemble(Box::new(SourceError {
+            message,
+            location: self.location,
+        })))
+    }
+}
+
+enum Target {
+    Index(usize),
+    Label(String),
+}
+
+struct Assembler<'a> {
+    version: Option<String>,
+    files: Vec<String>,
+    entry: Option<(Target, Line<'a>)>,
+    functions: Vec<(FunctionInfo, Target, Line<'a>)>,
+    labels: HashMap<String, usize>,
+    instructions: Vec<Instruction>,
+    locations: LineTable,
+    parents: HashMap<usize, usize>,
+    location: Option<TokenLocation>,
+    parent: Option<usize>,
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn unquote(line: &Line, text: &str) -> Result<String> {
+    let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    else {
+        return line.error(format!("Expected quoted string, found '{text}'."));
+    };
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('0') => result.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => result.push(c),
+            Some('u') => {
+                let code: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                match code
+                    .strip_prefix('{')
+                    .and_then(|code| u32::from_str_radix(code, 16).ok())
+                    .and_then(char::from_u32)
+                {
+                    Some(c) => result.push(c),
+                    None => return line.error(format!("Invalid unicode escape in {text}.")),
+                }
+            }
+            _ => return line.error(format!("Invalid escape sequence in {text}.")),
+        }
+    }
+    Ok(result)
+}
+
+fn parse<T: std::str::FromStr>(line: &Line, text: Option<&str>, what: &str) -> Result<T> {
+    match text.map(str::parse) {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(_)) => line.error(format!(
+            "Expected {what}, found '{}'.",
+            text.unwrap_or_default()
+        )),
+        None => line.error(format!("Expected {what}, found end of line.")),
+    }
+}
+
+fn target(line: &Line, text: Option<&str>) -> Result<Target> {
+    match text {
+        Some(text) if text.bytes().all(|c| c.is_ascii_digit()) => {
+            Ok(Target::Index(parse(line, Some(text), "instruction index")?))
+        }
+        Some(text) => Ok(Target::Label(text.to_string())),
+        None => line.error("Expected label or instruction index, found end of line.".to_string()),
+    }
+}
+
+fn register(line: &Line, text: Option<&str>) -> Result<Register> {
+    match text.map(|text| text.strip_prefix('r').map(str::parse)) {
+        Some(Some(Ok(register))) => Ok(register),
+        Some(_) => line.error(format!(
+            "Expected register, found '{}'.",
+            text.unwrap_or_default()
+        )),
+        None => line.error("Expected register, found end of line.".to_string()),
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    text.split_once(char::is_whitespace).unwrap_or((text, ""))
+}
+
+fn end_of_line<'a>(line: &Line, mut words: impl Iterator<Item = &'a str>) -> Result<()> {
+    match words.next() {
+        Some(word) => line.error(format!("Unexpected '{word}' at end of line.")),
+        None => Ok(()),
+    }
+}
+
+impl<'a> Assembler<'a> {
+    fn new() -> Self {
+        Self {
+            version: None,
+            files: Vec::new(),
+            entry: None,
+            functions: Vec::new(),
+            labels: HashMap::new(),
+            instructions: Vec::new(),
+            locations: LineTable::new(),
+            parents: HashMap::new(),
+            location: None,
+            parent: None,
+        }
+    }
+
+    fn location(&mut self, line: &Line, arguments: &str) -> Result<()> {
+        let mut words = arguments.split_whitespace();
+        let position = words.next().unwrap_or_default();
+        let Some((row, column)) = position.split_once(':') else {
+            return line.error(format!("Expected 'line:column', found '{position}'."));
+        };
+        let row: u32 = parse(line, Some(row), "line number")?;
+        let column: u32 = parse(line, Some(column), "column number")?;
+        if row == 0 || column == 0 {
+            return line.error("Lines and columns start at 1.".to_string());
+        }
+        let mut location = TokenLocation {
+            line: row - 1,
+            column: column - 1,
+            ..TokenLocation::default()
+        };
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                return line.error(format!("Expected 'key=value', found '{word}'."));
+            };
+            match key {
+                "offset" => location.offset = parse(line, Some(value), "offset")?,
+                "length" => location.length = parse(line, Some(value), "length")?,
+                "file" => location.file = FileId::new(parse(line, Some(value), "file index")?),
+                key => return line.error(format!("Unknown location attribute '{key}'.")),
+            }
+        }
+        self.location = Some(location);
+        Ok(())
+    }
+
+    fn directive(&mut self, line: Line<'a>, directive: &str, arguments: &str) -> Result<()> {
+        let mut words = arguments.split_whitespace();
+        match directive {
+            ".version" => self.version = Some(unquote(&line, arguments)?),
+            ".file" => self.files.push(unquote(&line, arguments)?),
+            ".entry" => {
+                let target = target(&line, words.next())?;
+                end_of_line(&line, words)?;
+                self.entry = Some((target, line));
+            }
+            ".function" => {
+                let Some(name) = words.next() else {
+                    return line.error("Expected function name, found end of line.".to_string());
+                };
+                let arity = parse(&line, words.next(), "function arity")?;
+                let target = target(&line, words.next())?;
+                end_of_line(&line, words)?;
+                let info = FunctionInfo {
+                    name: name.to_string(),
+                    arity,
+                    entry: 0,
+                };
+                self.functions.push((info, target, line));
+            }
+            ".loc" => self.location(&line, arguments)?,
+            ".parent" => {
+                self.parent = Some(parse(&line, words.next(), "instruction index")?);
+                end_of_line(&line, words)?;
+            }
+            directive => return line.error(format!("Unknown directive '{directive}'.")),
+        }
+        Ok(())
+    }
+
+    fn function_index(&self, line: &Line, operand: Option<&str>) -> Result<u32> {
+        match operand {
+            Some(text) if !text.bytes().all(|c| c.is_ascii_digit()) => {
+                match self
+                    .functions
+                    .iter()
+                    .position(|(info, ..)| info.name == text)
+                {
+                    Some(index) => Ok(index as u32),
+                    None => line.error(format!("Unknown function '{text}'.")),
+                }
+            }
+            operand => parse(line, operand, "function index"),
+        }
+    }
+
+    fn instruction(&mut self, line: &Line, text: &str) -> Result<()> {
+        let (mut mnemonic, mut rest) = split_word(text);
+        if mnemonic.bytes().all(|c| c.is_ascii_digit()) {
+            let index: usize = parse(line, Some(mnemonic), "instruction index")?;
+            if index != self.instructions.len() {
+                return line.error(format!(
+                    "Instruction index {index} does not match its position {}.",
+                    self.instructions.len()
+                ));
+            }
+            (mnemonic, rest) = split_word(rest);
+        }
+        let mut words = rest
+            .split(',')
+            .map(str::trim)
+            .filter(|word| !word.is_empty());
+        let instruction = match mnemonic {
+            "Integer" => Instruction::Integer(
+                register(line, words.next())?,
+                parse(line, words.next(), "integer")?,
+            ),
+            "Float" => Instruction::Float(
+                register(line, words.next())?,
+                parse(line, words.next(), "float")?,
+            ),
+            "Boolean" => Instruction::Boolean(
+                register(line, words.next())?,
+                parse(line, words.next(), "boolean")?,
+            ),
+            "Void" => Instruction::Void(register(line, words.next())?),
+            "Move" => {
+                Instruction::Move(register(line, words.next())?, register(line, words.next())?)
+            }
+            "Global" => Instruction::Global(
+                register(line, words.next())?,
+                parse(line, words.next(), "global slot")?,
+            ),
+            "SetGlobal" => Instruction::SetGlobal(
+                parse(line, words.next(), "global slot")?,
+                register(line, words.next())?,
+            ),
+            "Call" => Instruction::Call(
+                self.function_index(line, words.next())?,
+                register(line, words.next())?,
+            ),
+            "Return" => Instruction::Return(register(line, words.next())?),
+            "End" => Instruction::End(register(line, words.next())?),
+            mnemonic => {
+                if let Some(op) = BinaryOperator::ALL
+                    .into_iter()
+                    .find(|op| op.mnemonic() == mnemonic)
+                {
+                    Instruction::Binary(
+                        op,
+                        register(line, words.next())?,
+                        register(line, words.next())?,
+                        register(line, words.next())?,
+                    )
+                } else if let Some(op) = BinaryOperator::ALL
+                    .into_iter()
+                    .find(|op| op.const_mnemonic() == mnemonic)
+                {
+                    Instruction::BinaryConst(
+                        op,
+                        register(line, words.next())?,
+                        register(line, words.next())?,
+                        parse(line, words.next(), "integer")?,
+                    )
+                } else {
+                    return line.error(format!("Unknown instruction '{mnemonic}'."));
+                }
+            }
+        };
+        end_of_line(line, words)?;
+        let index = self.instructions.len();
+        if let Some(location) = self.location.take() {
+            self.locations.push(index, location);
+        }
+        if let Some(parent) = self.parent.take() {
+            self.parents.insert(index, parent);
+        }
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    fn line(&mut self, line: Line<'a>) -> Result<()> {
+        let text = strip_comment(line.text).trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        if text.starts_with('.') {
+            let (directive, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            return self.directive(line, directive, arguments.trim());
+        }
+        if let Some(label) = text.strip_suffix(':') {
+            if label.is_empty() || label.contains(char::is_whitespace) {
+                return line.error(format!("Invalid label '{label}'."));
+            }
+            if self
+                .labels
+                .insert(label.to_string(), self.instructions.len())
+                .is_some()
+            {
+                return line.error(format!("Label '{label}' is defined twice."));
+            }
+            return Ok(());
+        }
+        self.instruction(&line, text)
+    }
+
+    fn resolve(&self, target: &Target, line: &Line) -> Result<usize> {
+        let index = match target {
+            Target::Index(index) => *index,
+            Target::Label(label) => match self.labels.get(label) {
+                Some(index) => *index,
+                None => return line.error(format!("Unknown label '{label}'.")),
+            },
+        };
+        if index >= self.instructions.len() {
+            return line.error(format!("Target {index} is outside of the program."));
+        }
+        Ok(index)
+    }
+
+    fn finish(self, last: Line) -> Result<Program> {
+        if self.location.is_some() || self.parent.is_some() {
+            return last.error("Directive is not followed by an instruction.".to_string());
+        }
+        let entry = match &self.entry {
+            Some((target, line)) => self.resolve(target, line)?,
+            None => 0,
+        };
+        let mut functions = Vec::new();
+        for (info, target, line) in &self.functions {
+            functions.push(FunctionInfo {
+                entry: self.resolve(target, line)?,
+                ..info.clone()
+            });
+        }
+        let mut program = Program::unmeasured(
+            self.instructions.into_boxed_slice(),
+            self.locations,
+            self.parents,
+            functions,
+            entry,
+        );
+        if let Some(version) = self.version {
+            program.set_header(version, self.files);
+        } else {
+            program.set_header(program.version().to_string(), self.files);
+        }
+        // Hand-written code is checked like loaded bytecode before anything runs it.
+        program.verify()?;
+        Ok(program.measured())
+    }
+}
+
+impl Program {
+    pub fn assemble(text: &str) -> Result<Program> {
+        let mut assembler = Assembler::new();
+        let mut offset = 0;
+        let mut last = TokenLocation::default();
+        for (index, text) in text.split_inclusive('\n').enumerate() {
+            let trimmed = text.trim_end_matches(['\n', '\r']);
+            last = TokenLocation {
+                file: FileId::default(),
+                offset,
+                line: index as u32,
+                column: 0,
+                length: trimmed.len() as u32,
+            };
+            offset += text.len() as u32;
+            assembler.line(Line {
+                text: trimmed,
+                location: last,
+            })?;
+        }
+        assembler.finish(Line {
+            text: "",
+            location: last,
+        })
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use uniq::{CompileOptions, Debugger, Error, Program, Renderer, Repl, SourceMap, State, Value};
+
+const USAGE: &str = "\
+Usage: uniq [command] [arguments]
+
+Commands:
+    repl                                Start an interactive session (the default)
+    run <file.uq>                       Compile and run a script
+    debug <file.uq>                     Step through a script with breakpoints and watches
+    check <file.uq>                     Parse and compile a script without running it
+    compile <file.uq> -o <out> [--json] Compile a script to a bytecode file
+    exec <file.uqb>                     Run a compiled bytecode file
+    disasm <file>                       Print the bytecode of a script or bytecode file
+
+Options:
+    -O<level>                           Optimization level, 0 disables constant folding, 2 also
+                                        fuses instructions (default 2)
+
+Exit codes:
+    0 success, 2 usage, 3 I/O, serialization or invalid bytecode,
+    4 syntax, 5 compilation, 6 runtime";
+
+const EXIT_USAGE: u8 = 2;
+
+fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::Io(_) | Error::Json(_) | Error::Bincode(_) | Error::Verify(_) | Error::Format(_) => {
+            3
+        }
+        Error::Lex(_) | Error::Parse(_) | Error::Assemble(_) => 4,
+        Error::Compile(_) => 5,
+        Error::Runtime(_) => 6,
+    }
+}
+
+fn report(error: &Error, sources: &SourceMap) -> ExitCode {
+    let stderr = io::stderr();
+    let renderer = Renderer::new().with_color(stderr.is_terminal());
+    let _ = renderer.render(&mut stderr.lock(), &error.diagnostic(), sources);
+    ExitCode::from(exit_code(error))
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("{message}\n\n{USAGE}");
+    ExitCode::from(EXIT_USAGE)
+}
+
+fn is_json(path: &str) -> bool {
+    path.ends_with(".json")
+}
+
+fn load(path: &str) -> Result<Program, Error> {
+    if is_json(path) {
+        Program::load_json(path)
+    } else {
+        Program::load_bin(path)
+    }
+}
+
+fn program_sources(program: &Program) -> SourceMap {
+    let mut sources = SourceMap::new();
+    for file in program.files() {
+        let source = std::fs::read_to_string(file).unwrap_or_default();
+        sources.add(file.clone(), source);
+    }
+    sources
+}
+
+fn new_state(options: CompileOptions) -> State {
+    let mut state = State::new();
+    state.set_compile_options(options);
+    state
+}
+
+fn print_value(value: Value) {
+    if value != Value::Void {
+        println!("{value}");
+    }
+}
+
+fn run(path: &str, options: CompileOptions) -> ExitCode {
+    let mut state = new_state(options);
+    match state
+        .compile_path(path)
+        .and_then(|program| uniq::run(&program))
+    {
+        Ok(value) => {
+            print_value(value);
+            ExitCode::SUCCESS
+        }
+        Err(error) => report(&error, state.sources()),
+    }
+}
+
+fn check(path: &str, options: CompileOptions) -> ExitCode {
+    let mut state = new_state(options);
+    match state.compile_path(path) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => report(&error, state.sources()),
+    }
+}
+
+fn compile(path: &str, output: &str, json: bool, options: CompileOptions) -> ExitCode {
+    let mut state = new_state(options);
+    let result = state.compile_path(path).and_then(|program| {
+        if json {
+            program.save_json(output, true)
+        } else {
+            program.save_bin(output)
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&error, state.sources()),
+    }
+}
+
+fn exec(path: &str) -> ExitCode {
+    let program = match load(path) {
+        Ok(program) => program,
+        Err(error) => return report(&error, &SourceMap::new()),
+    };
+    match uniq::run(&program) {
+        Ok(value) => {
+            print_value(value);
+            ExitCode::SUCCESS
+        }
+        Err(error) => report(&error, &program_sources(&program)),
+    }
+}
+
+fn disasm(path: &str, options: CompileOptions) -> ExitCode {
+    let mut state = new_state(options);
+    let program = if path.ends_with(".uq") {
+        state.compile_path(path)
+    } else {
+        load(path)
+    };
+    match program {
+        Ok(program) => {
+            let source = program
+                .files()
+                .first()
+                .and_then(|file| std::fs::read_to_string(file).ok());
+            print!("{}", program.disassemble(source.as_deref()));
+            ExitCode::SUCCESS
+        }
+        Err(error) => report(&error, state.sources()),
+    }
+}
+
+fn debug(path: &str, options: CompileOptions) -> ExitCode {
+    let stdout = io::stdout();
+    let mut debugger = Debugger::empty(new_state(options))
+        .with_renderer(Renderer::new().with_color(stdout.is_terminal()));
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => return report(&Error::Io(error), debugger.state().sources()),
+    };
+    if let Err(error) = debugger.load(path, &source) {
+        return report(&error, debugger.state().sources());
+    }
+    match debugger.run(io::stdin().lock(), &mut stdout.lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&Error::Io(error), &SourceMap::new()),
+    }
+}
+
+fn repl(options: CompileOptions) -> ExitCode {
+    let stdout = io::stdout();
+    let mut repl = Repl::new()
+        .with_compile_options(options)
+        .with_renderer(Renderer::new().with_color(stdout.is_terminal()));
+    if let Some(home) = std::env::var_os("HOME") {
+        repl = repl.with_history(PathBuf::from(home).join(".uniq_history"));
+    }
+    match repl.run(io::stdin().lock(), &mut stdout.lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&Error::Io(error), &SourceMap::new()),
+    }
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = CompileOptions::new();
+    let mut rest = Vec::new();
+    for argument in &arguments {
+        match argument.strip_prefix("-O").map(str::parse) {
+            Some(Ok(level)) => options = options.with_optimization(level),
+            Some(Err(_)) => return usage(&format!("Invalid optimization level '{argument}'.")),
+            None => rest.push(argument.as_str()),
+        }
+    }
+    match rest.as_slice() {
+        ["run", path] => run(path, options),
+        ["check", path] => check(path, options),
+        ["debug", path] => debug(path, options),
+        ["compile", path, rest @ ..] => {
+            let mut output = None;
+            let mut json = false;
+            let mut rest = rest.iter();
+            while let Some(argument) = rest.next() {
+                match *argument {
+                    "-o" => match rest.next() {
+                        Some(path) => output = Some(*path),
+                        None => return usage("Missing path after '-o'."),
+                    },
+                    "--json" => json = true,
+                    argument => return usage(&format!("Unknown argument '{argument}'.")),
+                }
+            }
+            match output {
+                Some(output) => compile(path, output, json, options),
+                None => usage("Missing output path, use '-o <out>'."),
+            }
+        }
+        ["exec", path] => exec(path),
+        ["disasm", path] => disasm(path, options),
+        ["help" | "--help" | "-h"] => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        [] | ["repl"] => repl(options),
+        [command, ..] => usage(&format!("Unknown command or arguments for '{command}'.")),
+    }
+}
@@ -0,0 +1,35 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileOptions {
+    optimization: u8,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompileOptions {
+    pub const MAX_OPTIMIZATION: u8 = 2;
+
+    pub fn new() -> Self {
+        Self { optimization: 2 }
+    }
+
+    pub fn with_optimization(mut self, level: u8) -> Self {
+        self.optimization = level.min(Self::MAX_OPTIMIZATION);
+        self
+    }
+
+    pub fn optimization(&self) -> u8 {
+        self.optimization
+    }
+
+    pub fn folds_constants(&self) -> bool {
+        self.optimization >= 1
+    }
+
+    pub fn fuses_instructions(&self) -> bool {
+        self.optimization >= 2
+    }
+}
@@ -1,51 +1,573 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::{token::TokenLocation, Binary, Instruction, Node, Program, SourceResult};
+use crate::{
+    module::{Module, Modules},
+    peephole,
+    token::TokenLocation,
+    Binary, BinaryOperator, Block, Call, CompileOptions, Error, FileId, Function, FunctionInfo,
+    Identifier, Import, Instruction, Let, LineTable, Node, Program, Register, Result, SourceError,
+    Value,
+};
 
+#[derive(Clone)]
+struct Link {
+    module: Rc<Module>,
+    base: usize,
+}
+
+#[derive(Clone)]
 pub struct Compiler {
     instructions: Vec<Instruction>,
-    locations: HashMap<usize, TokenLocation>,
+    locations: LineTable,
+    parents: HashMap<usize, usize>,
+    functions: Vec<FunctionInfo>,
+    parameter_names: Vec<Vec<String>>,
+    names: HashMap<String, usize>,
+    imports: HashMap<String, usize>,
+    globals: HashMap<String, u32>,
+    links: Vec<Link>,
+    parameters: Vec<String>,
+    frame: Vec<String>,
+    top: u32,
+    entry: usize,
+    declared: usize,
+    linked: usize,
+    options: CompileOptions,
+}
+
+fn optimize(program: Program, options: CompileOptions) -> Program {
+    if options.fuses_instructions() {
+        peephole::optimize(&program)
+    } else {
+        program
+    }
+}
+
+fn error<T>(message: String, location: TokenLocation) -> Result<T> {
+    Err(Error::Compile(Box::new(SourceError { message, location })))
+}
+
+fn items(node: &Option<Node>) -> Vec<&Node> {
+    match node {
+        Some(Node::Block(block)) => block
+            .statements
+            .iter()
+            .chain(&block.result)
+            .filter(|node| node.is_item())
+            .collect(),
+        Some(node) if node.is_item() => vec![node],
+        _ => Vec::new(),
+    }
+}
+
+fn literal(instruction: Instruction) -> Option<Value> {
+    match instruction {
+        Instruction::Integer(_, value) => Some(Value::Integer(value)),
+        Instruction::Float(_, value) => Some(Value::Float(value)),
+        Instruction::Boolean(_, value) => Some(Value::Boolean(value)),
+        Instruction::Void(_) => Some(Value::Void),
+        _ => None,
+    }
+}
+
+fn constant(dst: Register, value: Value) -> Instruction {
+    match value {
+        Value::Integer(value) => Instruction::Integer(dst, value),
+        Value::Float(value) => Instruction::Float(dst, value),
+        Value::Boolean(value) => Instruction::Boolean(dst, value),
+        Value::Void => Instruction::Void(dst),
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
     pub fn new() -> Self {
+        Self::with_options(CompileOptions::default())
+    }
+
+    pub fn with_options(options: CompileOptions) -> Self {
         Self {
             instructions: Vec::new(),
-            locations: HashMap::new(),
+            locations: LineTable::new(),
+            parents: HashMap::new(),
+            functions: Vec::new(),
+            parameter_names: Vec::new(),
+            names: HashMap::new(),
+            imports: HashMap::new(),
+            globals: HashMap::new(),
+            links: Vec::new(),
+            parameters: Vec::new(),
+            frame: Vec::new(),
+            top: 0,
+            entry: 0,
+            declared: 0,
+            linked: 0,
+            options,
         }
     }
 
-    fn push(&mut self, instruction: Instruction) -> SourceResult<()> {
+    fn push(&mut self, instruction: Instruction) -> Result<Option<usize>> {
+        self.instructions.push(instruction);
+        Ok(None)
+    }
+
+    fn push_located(
+        &mut self,
+        instruction: Instruction,
+        location: TokenLocation,
+        children: &[Option<usize>],
+    ) -> Result<Option<usize>> {
+        let index = self.instructions.len();
+        for child in children.iter().flatten() {
+            self.parents.insert(*child, index);
+        }
+        self.locations.push(index, location);
         self.instructions.push(instruction);
+        Ok(Some(index))
+    }
+
+    fn allocate(&mut self, location: TokenLocation) -> Result<Register> {
+        let register = self.top;
+        if register > Register::MAX as u32 {
+            return error("Expression needs too many registers.".to_string(), location);
+        }
+        self.top += 1;
+        Ok(register as Register)
+    }
+
+    fn parameter(&self, name: &str) -> Option<Register> {
+        self.parameters
+            .iter()
+            .position(|parameter| parameter == name)
+            .map(|slot| slot as Register)
+    }
+
+    // Parameters already live in registers, anything else is compiled into `dst`.
+    fn operand(&mut self, node: &Node, dst: Register) -> Result<(Register, Option<usize>)> {
+        match node {
+            Node::Identifier(identifier) => match self.parameter(&identifier.name) {
+                Some(register) => Ok((register, None)),
+                None => Ok((dst, self.identifier(identifier, dst)?)),
+            },
+            node => Ok((dst, self.node(node, dst)?)),
+        }
+    }
+
+    fn fold(&self, operator: BinaryOperator, start: usize) -> Option<Value> {
+        if !self.options.folds_constants() || self.instructions.len() != start + 2 {
+            return None;
+        }
+        let left = literal(self.instructions[start])?;
+        let right = literal(self.instructions[start + 1])?;
+        // Failing operations stay in the code so that they report at runtime.
+        left.binary(operator, right).ok()
+    }
+
+    fn binary(&mut self, binary: &Binary, dst: Register) -> Result<Option<usize>> {
+        let start = self.instructions.len();
+        let top = self.top;
+        let (left, left_index) = self.operand(&binary.left, dst)?;
+        let temporary = self.allocate(binary.location)?;
+        let (right, right_index) = self.operand(&binary.right, temporary)?;
+        self.top = top;
+        if let Some(value) = self.fold(binary.operator, start) {
+            // The constant keeps the location of the expression it replaces.
+            self.instructions.truncate(start);
+            self.locations.truncate(start);
+            return self.push_located(constant(dst, value), binary.location, &[]);
+        }
+        self.push_located(
+            Instruction::Binary(binary.operator, dst, left, right),
+            binary.location,
+            &[left_index, right_index],
+        )
+    }
+
+    fn block(&mut self, block: &Block, dst: Register) -> Result<Option<usize>> {
+        // Statement values are thrown away, so they can borrow the result register.
+        for statement in block.statements.iter().filter(|node| !node.is_item()) {
+            self.operand(statement, dst)?;
+        }
+        match &block.result {
+            Some(result) if !result.is_item() => self.node(result, dst),
+            _ => self.push(Instruction::Void(dst)),
+        }
+    }
+
+    fn identifier(&mut self, identifier: &Identifier, dst: Register) -> Result<Option<usize>> {
+        let instruction = match self.parameter(&identifier.name) {
+            Some(register) => Instruction::Move(dst, register),
+            None => match self.globals.get(&identifier.name) {
+                Some(slot) => Instruction::Global(dst, *slot),
+                None => {
+                    return error(
+                        format!("Unknown variable '{}'.", identifier.name),
+                        identifier.location,
+                    )
+                }
+            },
+        };
+        self.push_located(instruction, identifier.location, &[])
+    }
+
+    fn variable(&mut self, variable: &Let, dst: Register) -> Result<Option<usize>> {
+        let (value, index) = self.operand(&variable.value, dst)?;
+        let next = self.globals.len() as u32;
+        let slot = *self.globals.entry(variable.name.clone()).or_insert(next);
+        self.push_located(
+            Instruction::SetGlobal(slot, value),
+            variable.location,
+            &[index],
+        )?;
+        self.push(Instruction::Void(dst))
+    }
+
+    fn resolve(&self, call: &Call) -> Result<usize> {
+        match &call.module {
+            None => match self.names.get(&call.name) {
+                Some(index) => Ok(*index),
+                None => error(format!("Unknown function '{}'.", call.name), call.location),
+            },
+            Some(alias) => {
+                let Some(link) = self.imports.get(alias).map(|link| &self.links[*link]) else {
+                    return error(format!("Unknown module '{alias}'."), call.location);
+                };
+                match link.module.exports.get(&call.name) {
+                    Some(index) => Ok(link.base + index),
+                    None => error(
+                        format!("Module '{alias}' has no function '{}'.", call.name),
+                        call.location,
+                    ),
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, call: &Call, dst: Register) -> Result<Option<usize>> {
+        let index = self.resolve(call)?;
+        let arity = self.functions[index].arity as usize;
+        if arity != call.arguments.len() {
+            return error(
+                format!(
+                    "Function '{}' expects {arity} arguments, found {}.",
+                    call.name,
+                    call.arguments.len()
+                ),
+                call.location,
+            );
+        }
+        // Arguments are placed in consecutive registers, the callee's frame starts at `base`
+        // and its result replaces the first argument.
+        let top = self.top;
+        let base = match dst as u32 + 1 == self.top {
+            true => dst,
+            false => self.allocate(call.location)?,
+        };
+        let mut children = Vec::new();
+        for (offset, argument) in call.arguments.iter().enumerate() {
+            let register = base as u32 + offset as u32;
+            while self.top <= register {
+                self.allocate(call.location)?;
+            }
+            children.push(self.node(argument, register as Register)?);
+        }
+        self.top = top;
+        let index = self.push_located(
+            Instruction::Call(index as u32, base),
+            call.location,
+            &children,
+        )?;
+        if base != dst {
+            self.push(Instruction::Move(dst, base))?;
+        }
+        Ok(index)
+    }
+
+    fn node(&mut self, node: &Node, dst: Register) -> Result<Option<usize>> {
+        match node {
+            Node::Boolean(value) => self.push(Instruction::Boolean(dst, *value)),
+            Node::Integer(value) => self.push(Instruction::Integer(dst, *value)),
+            Node::Float(value) => self.push(Instruction::Float(dst, *value)),
+            Node::Binary(binary) => self.binary(binary, dst),
+            Node::Block(block) => self.block(block, dst),
+            Node::Identifier(identifier) => self.identifier(identifier, dst),
+            Node::Call(call) => self.call(call, dst),
+            Node::Let(variable) => self.variable(variable, dst),
+            Node::Function(function) => error(
+                format!(
+                    "Function '{}' must be declared at the top level.",
+                    function.name
+                ),
+                function.location,
+            ),
+            Node::Import(import) => error(
+                "Imports must be declared at the top level.".to_string(),
+                import.location,
+            ),
+        }
+    }
+
+    fn import(&mut self, import: &Import, modules: Option<&mut Modules>) -> Result<()> {
+        let Some(modules) = modules else {
+            return error(
+                "Imports are only available when compiling through a State.".to_string(),
+                import.location,
+            );
+        };
+        let module = modules.import(&import.path, import.location)?;
+        if self
+            .imports
+            .get(&import.alias)
+            .is_some_and(|link| *link >= self.linked)
+        {
+            return error(
+                format!("Module '{}' is already imported.", import.alias),
+                import.location,
+            );
+        }
+        let base = self.functions.len();
+        self.functions.extend(module.functions.iter().cloned());
+        self.parameter_names
+            .extend(module.parameter_names.iter().cloned());
+        self.imports.insert(import.alias.clone(), self.links.len());
+        self.links.push(Link { module, base });
         Ok(())
     }
 
-    fn binary(&mut self, binary: &Binary) -> SourceResult<()> {
-        self.node(&binary.left)?;
-        self.node(&binary.right)?;
-        self.locations
-            .insert(self.instructions.len(), binary.location);
-        self.instructions.push(binary.instruction);
+    fn declare(&mut self, function: &Function) -> Result<usize> {
+        if self
+            .names
+            .get(&function.name)
+            .is_some_and(|index| *index >= self.declared)
+        {
+            return error(
+                format!("Function '{}' is already declared.", function.name),
+                function.location,
+            );
+        }
+        if function.parameters.len() > Register::MAX as usize {
+            return error(
+                format!("Function '{}' has too many parameters.", function.name),
+                function.location,
+            );
+        }
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            if function.parameters[..index].contains(parameter) {
+                return error(
+                    format!("Parameter '{parameter}' is declared twice."),
+                    function.location,
+                );
+            }
+        }
+        let index = self.functions.len();
+        self.functions.push(FunctionInfo {
+            name: function.name.clone(),
+            arity: function.parameters.len() as u32,
+            entry: 0,
+        });
+        self.parameter_names.push(function.parameters.clone());
+        self.names.insert(function.name.clone(), index);
+        Ok(index)
+    }
+
+    fn declarations<'n>(
+        &mut self,
+        node: &'n Option<Node>,
+        mut modules: Option<&mut Modules>,
+    ) -> Result<Vec<(usize, &'n Function)>> {
+        self.declared = self.functions.len();
+        let mut functions = Vec::new();
+        for item in items(node) {
+            match item {
+                Node::Import(import) => self.import(import, modules.as_deref_mut())?,
+                Node::Function(function) => functions.push((self.declare(function)?, &**function)),
+                _ => unreachable!(),
+            }
+        }
+        Ok(functions)
+    }
+
+    fn function(&mut self, index: usize, function: &Function) -> Result<()> {
+        self.functions[index].entry = self.instructions.len();
+        self.parameters = function.parameters.clone();
+        self.top = function.parameters.len() as u32;
+        let result = self.allocate(function.location)?;
+        let result = match &function.body {
+            Some(body) => self.operand(body, result)?.0,
+            None => {
+                self.push(Instruction::Void(result))?;
+                result
+            }
+        };
+        self.parameters.clear();
+        self.push(Instruction::Return(result))?;
         Ok(())
     }
 
-    fn node(&mut self, node: &Node) -> SourceResult<()> {
+    fn link(&mut self) {
+        for link in self.linked..self.links.len() {
+            let offset = self.instructions.len();
+            let Link { module, base } = self.links[link].clone();
+            self.instructions.extend(module.instructions.iter().map(
+                |instruction| match instruction {
+                    Instruction::Call(index, register) => {
+                        Instruction::Call(index + base as u32, *register)
+                    }
+                    instruction => *instruction,
+                },
+            ));
+            for (index, function) in module.functions.iter().enumerate() {
+                self.functions[base + index].entry = function.entry + offset;
+            }
+            for (index, location) in module.locations.entries() {
+                self.locations.push(index + offset, location);
+            }
+            for (index, parent) in &module.parents {
+                self.parents.insert(index + offset, parent + offset);
+            }
+        }
+        self.linked = self.links.len();
+    }
+
+    fn main(&mut self, node: &Option<Node>) -> Result<()> {
+        self.entry = self.instructions.len();
+        // Main code normally starts with an empty frame, see `set_frame`.
+        self.parameters = self.frame.clone();
+        self.top = self.frame.len() as u32;
+        let result = self.allocate(TokenLocation::default())?;
         match node {
-            Node::Boolean(value) => self.push(Instruction::Boolean(*value)),
-            Node::Integer(value) => self.push(Instruction::Integer(*value)),
-            Node::Float(value) => self.push(Instruction::Float(*value)),
-            Node::Binary(binary) => self.binary(binary),
+            Some(node) if !node.is_item() => self.node(node, result)?,
+            _ => self.push(Instruction::Void(result))?,
+        };
+        self.parameters.clear();
+        self.push(Instruction::End(result))?;
+        Ok(())
+    }
+
+    pub fn compile(&mut self, node: &Option<Node>, modules: Option<&mut Modules>) -> Result<()> {
+        let functions = self.declarations(node, modules)?;
+        self.main(node)?;
+        for (index, function) in functions {
+            self.function(index, function)?;
         }
+        self.link();
+        Ok(())
     }
 
-    pub fn compile(&mut self, node: &Option<Node>) -> SourceResult<()> {
-        if let Some(node) = node {
-            self.node(node)?;
+    // Compiles one input of an interactive session. Its main code goes last, so
+    // `discard_main` can drop it once it ran and the session keeps only functions.
+    pub(crate) fn compile_input(
+        &mut self,
+        node: &Option<Node>,
+        modules: Option<&mut Modules>,
+    ) -> Result<()> {
+        let functions = self.declarations(node, modules)?;
+        for (index, function) in functions {
+            self.function(index, function)?;
+        }
+        self.link();
+        self.main(node)
+    }
+
+    pub(crate) fn discard_main(&mut self) {
+        for index in self.entry..self.instructions.len() {
+            self.parents.remove(&index);
+        }
+        self.locations.truncate(self.entry);
+        self.instructions.truncate(self.entry);
+    }
+
+    pub(crate) fn function_count(&self) -> usize {
+        self.functions.len()
+    }
+
+    // Instructions of every function compiled so far, plus main code that was not discarded.
+    pub(crate) fn code_len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub(crate) fn compile_module(
+        &mut self,
+        node: &Option<Node>,
+        file: FileId,
+        modules: Option<&mut Modules>,
+    ) -> Result<()> {
+        let functions = self.declarations(node, modules)?;
+        let statements = match node {
+            Some(Node::Block(block)) => block.statements.iter().chain(&block.result).count(),
+            Some(_) => 1,
+            None => 0,
+        };
+        if statements != items(node).len() {
+            return error(
+                "Modules may only contain imports and functions.".to_string(),
+                TokenLocation {
+                    file,
+                    ..TokenLocation::default()
+                },
+            );
+        }
+        for (index, function) in functions {
+            self.function(index, function)?;
         }
-        self.push(Instruction::End)
+        self.link();
+        Ok(())
+    }
+
+    pub(crate) fn finish_module(self) -> Module {
+        let exports = self.names;
+        Module {
+            instructions: self.instructions,
+            locations: self.locations,
+            parents: self.parents,
+            functions: self.functions,
+            parameter_names: self.parameter_names,
+            exports,
+        }
+    }
+
+    // Lets the next main code read these names from its first registers, like a function body
+    // reads its parameters. The debugger evaluates expressions inside a paused frame this way.
+    pub(crate) fn set_frame(&mut self, parameters: Vec<String>) {
+        self.frame = parameters;
+    }
+
+    pub(crate) fn parameter_names(&self, function: usize) -> &[String] {
+        self.parameter_names
+            .get(function)
+            .map_or(&[], |names| names.as_slice())
+    }
+
+    pub(crate) fn globals(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.globals
+            .iter()
+            .map(|(name, slot)| (name.as_str(), *slot))
+    }
+
+    pub(crate) fn program(&self) -> Program {
+        let program = Program::new(
+            self.instructions.clone().into_boxed_slice(),
+            self.locations.clone(),
+            self.parents.clone(),
+            self.functions.clone(),
+            self.entry,
+        );
+        optimize(program, self.options)
     }
 
     pub fn finish(self) -> Program {
-        Program::new(self.instructions.into_boxed_slice(), self.locations)
+        let program = Program::new(
+            self.instructions.into_boxed_slice(),
+            self.locations,
+            self.parents,
+            self.functions,
+            self.entry,
+        );
+        optimize(program, self.options)
     }
 }
@@ -0,0 +1,455 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    compiler::Compiler, Error, FileId, Program, Renderer, Result, State, StepResult, TokenLocation,
+    Value,
+};
+
+const HELP: &str = "\
+Commands:
+    run                   Restart the script from the beginning
+    continue, c           Run until the next breakpoint or the end of the script
+    step, s               Run to the next line, entering calls
+    next, n               Run to the next line, stepping over calls
+    out, o                Run until the current function returns
+    break, b [file:]line  Set a breakpoint, without a line list the breakpoints
+    delete, d [file:]line Remove a breakpoint
+    where, bt             Print the call stack
+    stack                 Print the registers of the current frame
+    locals                Print the parameters of the current function, or the globals
+    print, p <expr>       Evaluate an expression in the paused frame
+    watch, w <expr>       Evaluate an expression every time the script pauses
+    unwatch <number>      Remove a watch expression
+    help, h               Show this message
+    quit, q               Leave the debugger (Ctrl-D works too)";
+
+// Where a paused run is, in source terms. Recursion revisits a line at another depth.
+type Position = (usize, Option<(FileId, u32)>);
+
+fn position(state: &State, program: &Program) -> Position {
+    let line = state
+        .location(program)
+        .map(|location| (location.file, location.line));
+    (state.frames().len(), line)
+}
+
+fn is_breakpoint(breakpoints: &BTreeSet<(usize, u32)>, position: Position) -> bool {
+    position
+        .1
+        .is_some_and(|(file, line)| breakpoints.contains(&(file.index(), line)))
+}
+
+pub struct Debugger {
+    state: State,
+    compiler: Compiler,
+    program: Program,
+    file: FileId,
+    breakpoints: BTreeSet<(usize, u32)>,
+    watches: Vec<String>,
+    renderer: Renderer,
+    // Source file that expressions are compiled from, replaced by every evaluation.
+    scratch: Option<FileId>,
+    // Nothing ran since the last restart, so a breakpoint on the first line has not been hit.
+    fresh: bool,
+}
+
+impl Debugger {
+    pub fn new(name: &str, source: &str) -> Result<Self> {
+        Self::with_state(State::new(), name, source)
+    }
+
+    pub fn with_state(state: State, name: &str, source: &str) -> Result<Self> {
+        let mut debugger = Self::empty(state);
+        debugger.load(name, source)?;
+        Ok(debugger)
+    }
+
+    // A debugger without a script, errors of `load` can then be rendered from its sources.
+    pub fn empty(state: State) -> Self {
+        let compiler = Compiler::with_options(state.compile_options());
+        let program = compiler.program();
+        Self {
+            state,
+            compiler,
+            program,
+            file: FileId::default(),
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            renderer: Renderer::new().with_color(false),
+            scratch: None,
+            fresh: true,
+        }
+    }
+
+    // Compiles the script once and starts it, replacing the one being debugged.
+    pub fn load(&mut self, name: &str, source: &str) -> Result<()> {
+        let mut compiler = Compiler::with_options(self.state.compile_options());
+        let file = self.state.add_source(name, source);
+        self.program = self.state.compile_with(&mut compiler, file)?;
+        self.compiler = compiler;
+        self.file = file;
+        self.breakpoints.clear();
+        self.restart()
+    }
+
+    pub fn with_renderer(mut self, renderer: Renderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    // The debugged script, breakpoints in imported modules use their own file.
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn location(&self) -> Option<TokenLocation> {
+        self.state.location(&self.program)
+    }
+
+    pub fn restart(&mut self) -> Result<()> {
+        self.fresh = true;
+        self.state.start(&self.program)
+    }
+
+    // Lines count from zero like `TokenLocation::line`. Returns false when no code starts on
+    // the line, so the breakpoint could never be hit.
+    pub fn set_breakpoint(&mut self, file: FileId, line: u32) -> bool {
+        let reachable = self
+            .program
+            .line_table()
+            .entries()
+            .any(|(_, location)| location.file == file && location.line == line);
+        if reachable {
+            self.breakpoints.insert((file.index(), line));
+        }
+        reachable
+    }
+
+    pub fn remove_breakpoint(&mut self, file: FileId, line: u32) -> bool {
+        self.breakpoints.remove(&(file.index(), line))
+    }
+
+    pub fn breakpoints(&self) -> Vec<(FileId, u32)> {
+        self.breakpoints
+            .iter()
+            .map(|(file, line)| (FileId::new(*file as u32), *line))
+            .collect()
+    }
+
+    fn run_until<P: FnMut(&State, Position) -> bool>(&mut self, mut predicate: P) -> StepResult {
+        self.fresh = false;
+        let program = &self.program;
+        self.state
+            .run_until(program, |state| predicate(state, position(state, program)))
+    }
+
+    // Runs until the script enters a line with a breakpoint, or ends.
+    pub fn resume(&mut self) -> StepResult {
+        let mut previous = position(&self.state, &self.program);
+        if self.fresh && is_breakpoint(&self.breakpoints, previous) {
+            self.fresh = false;
+            return StepResult::Paused;
+        }
+        let breakpoints = self.breakpoints.clone();
+        self.run_until(|_, current| {
+            // Coming back from a call into the rest of a line does not enter it again.
+            let entered = current != previous && current.0 >= previous.0;
+            previous = current;
+            entered && is_breakpoint(&breakpoints, current)
+        })
+    }
+
+    pub fn step_into(&mut self) -> StepResult {
+        let start = position(&self.state, &self.program);
+        self.run_until(|_, current| current.1.is_some() && current != start)
+    }
+
+    pub fn step_over(&mut self) -> StepResult {
+        let (depth, line) = position(&self.state, &self.program);
+        self.run_until(|_, current| {
+            current.0 < depth || (current.0 == depth && current.1.is_some() && current.1 != line)
+        })
+    }
+
+    pub fn step_out(&mut self) -> StepResult {
+        let depth = self.state.frames().len();
+        self.run_until(|_, current| current.0 < depth)
+    }
+
+    // The paused location followed by the call in every frame below it.
+    pub fn backtrace(&self) -> Vec<TokenLocation> {
+        let calls = self
+            .state
+            .frames()
+            .iter()
+            .rev()
+            .map(|frame| frame.return_address - 1);
+        [self.state.program_counter()]
+            .into_iter()
+            .chain(calls)
+            .filter_map(|index| self.program.location(index))
+            .collect()
+    }
+
+    // Registers of the current frame, the arguments of a call come first.
+    pub fn stack(&self) -> Vec<Value> {
+        self.state.stack()[self.state.frame_base()..].to_vec()
+    }
+
+    // Parameters of the current function, or the globals that are set when paused in main.
+    pub fn locals(&self) -> Vec<(String, Value)> {
+        match self.state.frames().last() {
+            Some(frame) => {
+                let stack = self.state.stack();
+                self.compiler
+                    .parameter_names(frame.function)
+                    .iter()
+                    .zip(stack.get(frame.base..).unwrap_or_default())
+                    .map(|(name, value)| (name.clone(), *value))
+                    .collect()
+            }
+            None => {
+                let globals = self.state.globals();
+                let mut locals: Vec<_> = self
+                    .compiler
+                    .globals()
+                    .filter_map(|(name, slot)| {
+                        let value = globals.get(slot as usize).copied().flatten()?;
+                        Some((slot, name.to_string(), value))
+                    })
+                    .collect();
+                locals.sort_by_key(|(slot, ..)| *slot);
+                locals
+                    .into_iter()
+                    .map(|(_, name, value)| (name, value))
+                    .collect()
+            }
+        }
+    }
+
+    // Evaluates `expression` on a copy of the paused run's globals, so it can read the current
+    // frame's parameters and call the script's functions without changing the run.
+    pub fn evaluate(&mut self, expression: &str) -> Result<Value> {
+        let (parameters, arguments): (Vec<_>, Vec<_>) = match self.state.frames() {
+            [] => (Vec::new(), Vec::new()),
+            _ => self.locals().into_iter().unzip(),
+        };
+        let mut compiler = self.compiler.clone();
+        compiler.set_frame(parameters);
+        let file = match self.scratch {
+            Some(file) => {
+                self.state.replace_source(file, "<watch>", expression);
+                file
+            }
+            None => {
+                let file = self.state.add_source("<watch>", expression);
+                self.scratch = Some(file);
+                file
+            }
+        };
+        let program = self.state.compile_input(&mut compiler, file)?;
+        let mut state = State::with_options(self.state.options());
+        state.set_globals(self.state.globals().to_vec());
+        state.execute_in_frame(&program, &arguments)
+    }
+
+    pub fn watch(&mut self, expression: &str) {
+        self.watches.push(expression.to_string());
+    }
+
+    pub fn unwatch(&mut self, index: usize) -> bool {
+        if index < self.watches.len() {
+            self.watches.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn watches(&mut self) -> Vec<(String, Result<Value>)> {
+        self.watches
+            .clone()
+            .into_iter()
+            .map(|expression| {
+                let value = self.evaluate(&expression);
+                (expression, value)
+            })
+            .collect()
+    }
+
+    fn report<W: Write>(&self, out: &mut W, error: &Error) -> io::Result<()> {
+        self.renderer
+            .render(out, &error.diagnostic(), self.state.sources())
+    }
+
+    fn describe(&self, location: TokenLocation) -> String {
+        let file = self.state.sources().get(location.file);
+        let name = file.map_or("<unknown>", |file| file.name());
+        let text = file
+            .and_then(|file| file.source().lines().nth(location.line as usize))
+            .unwrap_or_default();
+        format!(
+            "{name}:{}:{}\n{:>4} | {text}",
+            location.line + 1,
+            location.column + 1,
+            location.line + 1
+        )
+    }
+
+    fn show<W: Write>(&mut self, out: &mut W, result: StepResult) -> io::Result<()> {
+        match result {
+            StepResult::Running | StepResult::Paused => {
+                match self.location() {
+                    Some(location) => writeln!(out, "{}", self.describe(location))?,
+                    None => writeln!(
+                        out,
+                        "Paused at instruction {}",
+                        self.state.program_counter()
+                    )?,
+                }
+                // Errors point into the scratch file, so each is reported before the next watch runs.
+                for (index, expression) in self.watches.clone().into_iter().enumerate() {
+                    match self.evaluate(&expression) {
+                        Ok(value) => writeln!(out, "{index}: {expression} = {value}")?,
+                        Err(error) => {
+                            writeln!(out, "{index}: {expression} =")?;
+                            self.report(out, &error)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            StepResult::Finished(value) => writeln!(out, "Finished with {value}"),
+            StepResult::Failed(error) => self.report(out, &error),
+        }
+    }
+
+    // Accepts `line` in the debugged script or `name:line` in any loaded file.
+    fn parse_line(&self, argument: &str) -> Option<(FileId, u32)> {
+        let (file, line) = match argument.rsplit_once(':') {
+            Some((name, line)) => {
+                let sources = self.state.sources();
+                // Other files may share the script's name, like an earlier load of it.
+                let file = match sources.get(self.file) {
+                    Some(file) if file.name() == name => self.file,
+                    _ => sources.files().find(|(_, file)| file.name() == name)?.0,
+                };
+                (file, line)
+            }
+            None => (self.file, argument),
+        };
+        let line: u32 = line.parse().ok()?;
+        Some((file, line.checked_sub(1)?))
+    }
+
+    fn command<W: Write>(&mut self, out: &mut W, input: &str) -> io::Result<bool> {
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+        match command {
+            "quit" | "q" => return Ok(false),
+            "help" | "h" => writeln!(out, "{HELP}")?,
+            "run" | "r" => match self.restart() {
+                Ok(()) => {
+                    let result = self.resume();
+                    self.show(out, result)?
+                }
+                Err(error) => self.report(out, &error)?,
+            },
+            "continue" | "c" => {
+                let result = self.resume();
+                self.show(out, result)?
+            }
+            "step" | "s" => {
+                let result = self.step_into();
+                self.show(out, result)?
+            }
+            "next" | "n" => {
+                let result = self.step_over();
+                self.show(out, result)?
+            }
+            "out" | "o" => {
+                let result = self.step_out();
+                self.show(out, result)?
+            }
+            "break" | "b" if argument.is_empty() => {
+                for (file, line) in self.breakpoints() {
+                    let name = self
+                        .state
+                        .sources()
+                        .get(file)
+                        .map_or("", |file| file.name());
+                    writeln!(out, "{name}:{}", line + 1)?;
+                }
+            }
+            "break" | "b" => match self.parse_line(argument) {
+                Some((file, line)) if self.set_breakpoint(file, line) => {}
+                Some(_) => writeln!(out, "No code starts on line '{argument}'.")?,
+                None => writeln!(out, "Invalid line '{argument}'.")?,
+            },
+            "delete" | "d" => match self.parse_line(argument) {
+                Some((file, line)) if self.remove_breakpoint(file, line) => {}
+                _ => writeln!(out, "No breakpoint on line '{argument}'.")?,
+            },
+            "where" | "bt" => {
+                for location in self.backtrace() {
+                    writeln!(out, "{}", self.describe(location))?;
+                }
+            }
+            "stack" => {
+                for (register, value) in self.stack().into_iter().enumerate() {
+                    writeln!(out, "r{register} = {value}")?;
+                }
+            }
+            "locals" => {
+                for (name, value) in self.locals() {
+                    writeln!(out, "{name} = {value}")?;
+                }
+            }
+            "print" | "p" => match self.evaluate(argument) {
+                Ok(value) => writeln!(out, "{value}")?,
+                Err(error) => self.report(out, &error)?,
+            },
+            "watch" | "w" => self.watch(argument),
+            "unwatch" => {
+                if !argument.parse().is_ok_and(|index| self.unwatch(index)) {
+                    writeln!(out, "No watch expression number '{argument}'.")?;
+                }
+            }
+            command => writeln!(
+                out,
+                "Unknown command '{command}', type 'help' for a list of commands."
+            )?,
+        }
+        Ok(true)
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, out: &mut W) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            write!(out, "(debug) ")?;
+            out.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(());
+            }
+            let command = line.trim();
+            if !command.is_empty() && !self.command(out, command)? {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::io::{self, Write};
+
+use crate::{token::TokenLocation, Error, RuntimeErrorKind, SourceMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<TokenLocation>,
+    pub hint: Option<String>,
+    pub note: Option<String>,
+    pub trace: Vec<TokenLocation>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, location: Option<TokenLocation>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message,
+            location,
+            hint: None,
+            note: None,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_hint(mut self, hint: String) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn with_trace(mut self, trace: Vec<TokenLocation>) -> Self {
+        self.trace = trace;
+        self
+    }
+}
+
+impl RuntimeErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DivisionByZero => "E0100",
+            Self::TypeMismatch { .. } => "E0101",
+            Self::StackOverflow => "E0102",
+            Self::StackUnderflow => "E0103",
+            Self::ProgramCounterOutOfBounds => "E0104",
+            Self::InvalidFunction(_) => "E0105",
+            Self::UndefinedGlobal(_) => "E0107",
+            Self::OutOfFuel => "E0108",
+            Self::Cancelled => "E0109",
+            Self::TimedOut => "E0110",
+            Self::OutOfMemory => "E0111",
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Self::DivisionByZero => Some("make sure the divisor is never zero".to_string()),
+            Self::TypeMismatch { .. } => {
+                Some("arithmetic and comparison require integer or float operands".to_string())
+            }
+            Self::StackOverflow => Some(
+                "check for unbounded recursion or split the expression into smaller parts"
+                    .to_string(),
+            ),
+            Self::UndefinedGlobal(_) => {
+                Some("make sure the variable is assigned before it is used".to_string())
+            }
+            Self::OutOfFuel => {
+                Some("raise the fuel limit, or refuel the state and resume the program".to_string())
+            }
+            Self::TimedOut => Some("raise the timeout or make the script do less work".to_string()),
+            Self::OutOfMemory => Some(
+                "raise the memory limit or keep fewer values alive at the same time".to_string(),
+            ),
+            Self::StackUnderflow
+            | Self::Cancelled
+            | Self::ProgramCounterOutOfBounds
+            | Self::InvalidFunction(_) => None,
+        }
+    }
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Lex(_) => "E0001",
+            Self::Parse(_) => "E0002",
+            Self::Compile(_) => "E0003",
+            Self::Assemble(_) => "E0004",
+            Self::Runtime(error) => error.kind.code(),
+            Self::Io(_) => "E0200",
+            Self::Json(_) => "E0201",
+            Self::Bincode(_) => "E0202",
+            Self::Verify(_) => "E0203",
+            Self::Format(_) => "E0204",
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let code = self.code();
+        match self {
+            Self::Lex(error) => {
+                Diagnostic::error(code, error.message.clone(), Some(error.location))
+                    .with_note("the lexer could not read this part of the source".to_string())
+            }
+            Self::Parse(error) | Self::Compile(error) | Self::Assemble(error) => {
+                Diagnostic::error(code, error.message.clone(), Some(error.location))
+            }
+            Self::Runtime(error) => {
+                let diagnostic = Diagnostic::error(code, error.kind.to_string(), error.location())
+                    .with_trace(error.trace.clone());
+                match error.kind.hint() {
+                    Some(hint) => diagnostic.with_hint(hint),
+                    None => diagnostic,
+                }
+            }
+            Self::Verify(error) => {
+                Diagnostic::error(code, format!("Invalid bytecode: {error}"), None)
+                    .with_note("the program was rejected before execution".to_string())
+            }
+            Self::Format(error) => Diagnostic::error(code, error.to_string(), None).with_hint(
+                "recompile the program from source with this version of uniq".to_string(),
+            ),
+            Self::Io(error) => Diagnostic::error(code, format!("I/O error: {error}"), None),
+            Self::Json(error) => {
+                Diagnostic::error(code, format!("JSON serialization error: {error}"), None)
+            }
+            Self::Bincode(error) => {
+                Diagnostic::error(code, format!("Binary serialization error: {error}"), None)
+            }
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+struct Line<'a> {
+    text: &'a str,
+    start: usize,
+}
+
+fn split_lines(source: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for text in source.split_inclusive('\n') {
+        let trimmed = text.strip_suffix('\n').unwrap_or(text);
+        lines.push(Line {
+            text: trimmed.strip_suffix('\r').unwrap_or(trimmed),
+            start,
+        });
+        start += text.len();
+    }
+    if lines.is_empty() {
+        lines.push(Line { text: "", start });
+    }
+    lines
+}
+
+pub struct Renderer {
+    color: bool,
+    context: usize,
+    tab_width: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            color: true,
+            context: 1,
+            tab_width: 4,
+        }
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    fn paint(&self, style: &'static str) -> &'static str {
+        if self.color {
+            style
+        } else {
+            ""
+        }
+    }
+
+    fn expand_tabs(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut width = 0;
+        for c in text.chars() {
+            if c == '\t' {
+                let spaces = self.tab_width - width % self.tab_width;
+                result.extend(std::iter::repeat_n(' ', spaces));
+                width += spaces;
+            } else {
+                result.push(c);
+                width += 1;
+            }
+        }
+        result
+    }
+
+    fn visual_width(&self, text: &str) -> usize {
+        self.expand_tabs(text).chars().count()
+    }
+
+    fn prefix<'a>(&self, text: &'a str, column: usize) -> &'a str {
+        let mut column = column.min(text.len());
+        while !text.is_char_boundary(column) {
+            column -= 1;
+        }
+        &text[..column]
+    }
+
+    fn gutter<W: Write>(&self, out: &mut W, width: usize, number: Option<usize>) -> io::Result<()> {
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+        match number {
+            Some(number) => write!(out, "{blue}{number:>width$} |{reset}"),
+            None => write!(out, "{blue}{:width$} |{reset}", ""),
+        }
+    }
+
+    fn snippet<W: Write>(
+        &self,
+        out: &mut W,
+        location: TokenLocation,
+        name: &str,
+        source: &str,
+        accent: &str,
+    ) -> io::Result<usize> {
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+        let mut lines = split_lines(source);
+        // Errors at the end of input point at the empty line after a final newline.
+        if location.line as usize == lines.len() && source.ends_with('\n') {
+            lines.push(Line {
+                text: "",
+                start: source.len(),
+            });
+        }
+        let first = (location.line as usize).min(lines.len() - 1);
+        // A location past the end of the source is shown at the end of its last line.
+        let column = if first == location.line as usize {
+            location.column as usize
+        } else {
+            lines[first].text.chars().count()
+        };
+        // Walk the span through the source so that it may continue on following lines.
+        let begin = location.offset as usize;
+        let end = begin + (location.length as usize).max(1);
+        let mut last = first;
+        while last + 1 < lines.len() && lines[last + 1].start < end {
+            last += 1;
+        }
+        let shown_last = (last + self.context).min(lines.len() - 1);
+        let width = (shown_last + 1).to_string().len();
+        writeln!(
+            out,
+            "{:width$}{blue}-->{reset} {name}:{}:{}",
+            "",
+            first + 1,
+            column + 1
+        )?;
+        self.gutter(out, width, None)?;
+        writeln!(out)?;
+        for (index, line) in lines
+            .iter()
+            .enumerate()
+            .take(shown_last + 1)
+            .skip(first.saturating_sub(self.context))
+        {
+            self.gutter(out, width, Some(index + 1))?;
+            writeln!(out, " {}", self.expand_tabs(line.text))?;
+            if index < first || index > last {
+                continue;
+            }
+            let from = begin.max(line.start) - line.start;
+            let to =
+                (end.min(line.start + line.text.len()).max(line.start) - line.start).max(from + 1);
+            let padding = self.visual_width(self.prefix(line.text, from));
+            let marks = (self.visual_width(self.prefix(line.text, to)) - padding).max(1);
+            self.gutter(out, width, None)?;
+            writeln!(out, " {:padding$}{accent}{}{reset}", "", "^".repeat(marks))?;
+        }
+        Ok(width)
+    }
+
+    pub fn render<W: Write>(
+        &self,
+        out: &mut W,
+        diagnostic: &Diagnostic,
+        sources: &SourceMap,
+    ) -> io::Result<()> {
+        let (bold, reset) = (self.paint(BOLD), self.paint(RESET));
+        let (label, accent) = match diagnostic.severity {
+            Severity::Error => ("error", self.paint(RED)),
+            Severity::Warning => ("warning", self.paint(YELLOW)),
+        };
+        writeln!(
+            out,
+            "{accent}{label}[{}]{reset}{bold}: {}{reset}",
+            diagnostic.code, diagnostic.message
+        )?;
+
+        let width = match diagnostic.location {
+            Some(location) => match sources.get(location.file) {
+                Some(file) => self.snippet(out, location, file.name(), file.source(), accent)?,
+                None => {
+                    writeln!(
+                        out,
+                        "{}-->{reset} <unknown>:{}:{}",
+                        self.paint(BLUE),
+                        location.line + 1,
+                        location.column + 1
+                    )?;
+                    0
+                }
+            },
+            None => 0,
+        };
+
+        let cyan = self.paint(CYAN);
+        if let Some(hint) = &diagnostic.hint {
+            writeln!(out, "{:width$} {cyan}= hint:{reset} {hint}", "")?;
+        }
+        if let Some(note) = &diagnostic.note {
+            writeln!(out, "{:width$} {cyan}= note:{reset} {note}", "")?;
+        }
+        if diagnostic.trace.len() > 1 {
+            writeln!(out, "{bold}stack backtrace:{reset}")?;
+            let mut repeated = 0;
+            for (index, location) in diagnostic.trace.iter().enumerate() {
+                if index > 0 && diagnostic.trace[index - 1] == *location {
+                    repeated += 1;
+                    continue;
+                }
+                if repeated > 0 {
+                    writeln!(out, "      [previous frame repeated {repeated} more times]")?;
+                    repeated = 0;
+                }
+                let file = sources.get(location.file);
+                write!(
+                    out,
+                    "{index:>4}: {}:{}:{}",
+                    file.map_or("<unknown>", |file| file.name()),
+                    location.line + 1,
+                    location.column + 1
+                )?;
+                let begin = location.offset as usize;
+                let text = file
+                    .and_then(|file| file.source().get(begin..begin + location.length as usize))
+                    .and_then(|text| text.lines().next());
+                match text {
+                    Some(text) => writeln!(out, " `{text}`")?,
+                    None => writeln!(out)?,
+                }
+            }
+            if repeated > 0 {
+                writeln!(out, "      [previous frame repeated {repeated} more times]")?;
+            }
+        }
+        Ok(())
+    }
+}
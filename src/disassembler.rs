@@ -0,0 +1,93 @@
+use std::{collections::HashMap, fmt::Write, ops::Range};
+
+use crate::{FileId, Instruction, Program};
+
+impl Program {
+    fn labels(&self) -> HashMap<usize, Vec<(String, String)>> {
+        let mut labels: HashMap<usize, Vec<(String, String)>> = HashMap::new();
+        labels
+            .entry(self.entry())
+            .or_default()
+            .push(("@main".to_string(), "main".to_string()));
+        for (index, function) in self.functions().iter().enumerate() {
+            labels.entry(function.entry).or_default().push((
+                format!("@f{index}"),
+                format!("fn {}/{}", function.name, function.arity),
+            ));
+        }
+        labels
+    }
+
+    pub fn disassemble(&self, source: Option<&str>) -> String {
+        let lines: Vec<&str> = source.map_or_else(Vec::new, |source| source.lines().collect());
+        let mut out = String::new();
+        let _ = writeln!(out, ".version {:?}", self.version());
+        for file in self.files() {
+            let _ = writeln!(out, ".file {file:?}");
+        }
+        let _ = writeln!(out, ".entry @main");
+        for (index, function) in self.functions().iter().enumerate() {
+            let _ = writeln!(
+                out,
+                ".function {} {} @f{index}",
+                function.name, function.arity
+            );
+        }
+        self.write_instructions(&mut out, &lines, 0..self.instructions().len());
+        out
+    }
+
+    fn write_instructions(&self, out: &mut String, lines: &[&str], range: Range<usize>) {
+        let labels = self.labels();
+        let mut shown = None;
+        for (index, instruction) in self
+            .instructions()
+            .iter()
+            .enumerate()
+            .take(range.end)
+            .skip(range.start)
+        {
+            for (label, comment) in labels.get(&index).into_iter().flatten() {
+                let _ = writeln!(out, "\n{label}:  ; {comment}");
+            }
+            if let Some(location) = self.line_table().starts_at(index) {
+                if location.file == FileId::default() && shown != Some(location.line) {
+                    if let Some(text) = lines.get(location.line as usize) {
+                        let _ = writeln!(out, "; {:>4} | {text}", location.line + 1);
+                    }
+                    shown = Some(location.line);
+                }
+                let _ = write!(
+                    out,
+                    "    .loc {}:{} offset={} length={}",
+                    location.line + 1,
+                    location.column + 1,
+                    location.offset,
+                    location.length
+                );
+                if location.file != FileId::default() {
+                    let _ = write!(out, " file={}", location.file.index());
+                }
+                let _ = writeln!(out);
+            }
+            if let Some(parent) = self.parents().get(&index) {
+                let _ = writeln!(out, "    .parent {parent}");
+            }
+            let _ = write!(out, "    {index:04}  {instruction}");
+            if let Instruction::Call(function, _) = instruction {
+                if let Some(function) = self.function(*function as usize) {
+                    let _ = write!(out, "  ; {}", function.name);
+                }
+            }
+            let _ = writeln!(out);
+        }
+    }
+
+    // Lists only the instructions in `range`, without the header that makes a listing
+    // assemble again.
+    pub(crate) fn disassemble_range(&self, range: Range<usize>) -> String {
+        let mut out = String::new();
+        self.write_instructions(&mut out, &[], range);
+        out
+    }
+}
@@ -0,0 +1,89 @@
+use std::{error, fmt, io};
+
+use crate::{token::TokenLocation, FormatError, RuntimeError, SourceError, VerifyError};
+
+#[derive(Debug)]
+pub enum Error {
+    Lex(Box<SourceError>),
+    Parse(Box<SourceError>),
+    Compile(Box<SourceError>),
+    Assemble(Box<SourceError>),
+    Runtime(Box<RuntimeError>),
+    Verify(Box<VerifyError>),
+    Format(FormatError),
+    Io(io::Error),
+    Json(serde_json::Error),
+    Bincode(bincode::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn location(&self) -> Option<TokenLocation> {
+        match self {
+            Self::Lex(error)
+            | Self::Parse(error)
+            | Self::Compile(error)
+            | Self::Assemble(error) => Some(error.location),
+            Self::Runtime(error) => error.location(),
+            Self::Verify(_) | Self::Format(_) | Self::Io(_) | Self::Json(_) | Self::Bincode(_) => {
+                None
+            }
+        }
+    }
+}
+
+fn write_phase(
+    f: &mut fmt::Formatter,
+    phase: &str,
+    location: Option<TokenLocation>,
+    message: &dyn fmt::Display,
+) -> fmt::Result {
+    match location {
+        Some(location) => write!(
+            f,
+            "{phase} error at line {}, column {}: {message}",
+            location.line + 1,
+            location.column + 1
+        ),
+        None => write!(f, "{phase} error: {message}"),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lex(error) => write_phase(f, "Lexing", Some(error.location), error),
+            Self::Parse(error) => write_phase(f, "Parsing", Some(error.location), error),
+            Self::Compile(error) => write_phase(f, "Compilation", Some(error.location), error),
+            Self::Assemble(error) => write_phase(f, "Assembly", Some(error.location), error),
+            Self::Runtime(error) => write_phase(f, "Runtime", error.location(), error),
+            Self::Verify(error) => write!(f, "Bytecode verification error: {error}"),
+            Self::Format(error) => write!(f, "Bytecode format error: {error}"),
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+            Self::Json(error) => write!(f, "JSON serialization error: {error}"),
+            Self::Bincode(error) => write!(f, "Binary serialization error: {error}"),
+        }
+    }
+}
+
+// Display already includes the wrapped error, so it is not repeated as a source.
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(error: bincode::Error) -> Self {
+        Self::Bincode(error)
+    }
+}
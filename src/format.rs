@@ -0,0 +1,147 @@
+use std::{error, fmt, io::Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    legacy::{ProgramV0, ProgramV1, ProgramV3},
+    Error, Program, Result,
+};
+
+pub const MAGIC: [u8; 4] = *b"UNIQ";
+pub const FORMAT_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Binary,
+    Json,
+    PrettyJson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    BadMagic,
+    Unsupported { found: u32, supported: u32 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a uniq bytecode file, the 'UNIQ' header is missing"),
+            Self::Unsupported { found, supported } if found > supported => write!(
+                f,
+                "Bytecode format {found} was produced by a newer uniq, this build reads formats up to {supported}"
+            ),
+            Self::Unsupported { found, supported } => write!(
+                f,
+                "Bytecode format {found} is no longer supported, this build reads format {supported}"
+            ),
+        }
+    }
+}
+
+impl error::Error for FormatError {}
+
+pub enum Payload {
+    Binary(Vec<u8>),
+    Json(serde_json::Value),
+}
+
+pub type MigrationHook = fn(format: u32, payload: Payload) -> Result<Program>;
+
+fn unsupported<T>(format: u32) -> Result<T> {
+    Err(Error::Format(FormatError::Unsupported {
+        found: format,
+        supported: FORMAT_VERSION,
+    }))
+}
+
+fn decode<T: DeserializeOwned>(payload: Payload) -> Result<T> {
+    Ok(match payload {
+        Payload::Binary(bytes) => bincode::deserialize(&bytes)?,
+        Payload::Json(value) => serde_json::from_value(value)?,
+    })
+}
+
+pub fn migrate(format: u32, payload: Payload) -> Result<Program> {
+    let stack = match format {
+        // Format 0 is the header-less layout of 0.1.0, written before format versions existed.
+        0 => decode::<ProgramV0>(payload)
+            .map_err(|_| Error::Format(FormatError::BadMagic))?
+            .into(),
+        1 => decode::<ProgramV1>(payload)?.into(),
+        // Formats 2 and 3 hold stack bytecode, it is translated to registers on load.
+        2 | 3 => decode::<ProgramV3>(payload)?,
+        format => return unsupported(format),
+    };
+    stack.translate()
+}
+
+#[derive(Serialize)]
+struct JsonHeader<'a> {
+    magic: &'a str,
+    format: u32,
+    #[serde(flatten)]
+    program: &'a Program,
+}
+
+pub(crate) fn write_json<W: Write>(program: &Program, writer: W, pretty: bool) -> Result<()> {
+    let header = JsonHeader {
+        magic: std::str::from_utf8(&MAGIC).unwrap_or_default(),
+        format: FORMAT_VERSION,
+        program,
+    };
+    if pretty {
+        serde_json::to_writer_pretty(writer, &header)?;
+    } else {
+        serde_json::to_writer(writer, &header)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_json(mut value: serde_json::Value, hook: MigrationHook) -> Result<Program> {
+    let Some(object) = value.as_object_mut() else {
+        return Err(Error::Format(FormatError::BadMagic));
+    };
+    let magic = object.remove("magic");
+    let format = object.remove("format");
+    let program = match (magic, format) {
+        (None, None) => hook(0, Payload::Json(value))?,
+        (Some(magic), Some(format)) if magic.as_str().map(str::as_bytes) == Some(&MAGIC) => {
+            match format
+                .as_u64()
+                .and_then(|format| u32::try_from(format).ok())
+            {
+                Some(FORMAT_VERSION) => serde_json::from_value(value)?,
+                Some(format) => hook(format, Payload::Json(value))?,
+                None => return Err(Error::Format(FormatError::BadMagic)),
+            }
+        }
+        _ => return Err(Error::Format(FormatError::BadMagic)),
+    };
+    // Verifying first keeps measuring linear, regions of verified programs do not overlap.
+    program.verify()?;
+    Ok(program.measured())
+}
+
+pub(crate) fn write_bin<W: Write>(program: &Program, mut writer: W) -> Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    bincode::serialize_into(writer, program)?;
+    Ok(())
+}
+
+pub(crate) fn read_bin(bytes: Vec<u8>, hook: MigrationHook) -> Result<Program> {
+    let program = match bytes.strip_prefix(&MAGIC) {
+        Some(rest) if rest.len() >= 4 => {
+            let (format, payload) = rest.split_at(4);
+            match u32::from_le_bytes([format[0], format[1], format[2], format[3]]) {
+                FORMAT_VERSION => bincode::deserialize(payload)?,
+                format => hook(format, Payload::Binary(payload.to_vec()))?,
+            }
+        }
+        Some(_) => return Err(Error::Format(FormatError::BadMagic)),
+        None => hook(0, Payload::Binary(bytes))?,
+    };
+    program.verify()?;
+    Ok(program.measured())
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use crate::{
+    BinaryOperator, Error, Instruction, Program, Result, RuntimeErrorKind, SourceError, Value,
+};
+
+// Stands for every value of the same type, the operators only look at types for these.
+fn representative(value: Value) -> Value {
+    match value {
+        Value::Void => Value::Void,
+        Value::Boolean(_) => Value::Boolean(true),
+        Value::Integer(_) => Value::Integer(1),
+        Value::Float(_) => Value::Float(1.0),
+    }
+}
+
+// Runs the code on types instead of values. Without branches every run takes the same path,
+// so the type of the result is known without running anything.
+struct Inference<'p> {
+    program: &'p Program,
+    globals: Vec<Option<Value>>,
+    active: Vec<bool>,
+    // Results of calls by callee and argument types, valid until a global changes type.
+    calls: HashMap<(usize, Vec<&'static str>), Value>,
+    generation: usize,
+}
+
+impl Inference<'_> {
+    fn error<T>(&self, message: String, index: usize) -> Result<T> {
+        Err(Error::Compile(Box::new(SourceError {
+            message,
+            location: self.program.location(index).unwrap_or_default(),
+        })))
+    }
+
+    fn binary(&self, index: usize, op: BinaryOperator, left: Value, right: Value) -> Result<Value> {
+        match left.binary(op, right) {
+            Ok(value) => Ok(representative(value)),
+            Err(kind) => match *kind {
+                RuntimeErrorKind::TypeMismatch { op, left, right } => self.error(
+                    format!(
+                        "Unable to {} {} and {}.",
+                        op.verb(),
+                        left.type_name(),
+                        right.type_name()
+                    ),
+                    index,
+                ),
+                kind => self.error(kind.to_string(), index),
+            },
+        }
+    }
+
+    fn call(&mut self, index: usize, function: usize, arguments: Vec<Value>) -> Result<Value> {
+        let Some(info) = self.program.function(function) else {
+            return self.error(format!("Call to unknown function {function}."), index);
+        };
+        if self.active[function] {
+            return self.error(format!("Calls to '{}' never return.", info.name), index);
+        }
+        let key = (
+            function,
+            arguments.iter().map(Value::type_name).collect::<Vec<_>>(),
+        );
+        if let Some(value) = self.calls.get(&key) {
+            return Ok(*value);
+        }
+        let generation = self.generation;
+        self.active[function] = true;
+        let value = self.region(info.entry, arguments);
+        self.active[function] = false;
+        let value = value?;
+        if generation == self.generation {
+            self.calls.insert(key, value);
+        }
+        Ok(value)
+    }
+
+    fn region(&mut self, start: usize, arguments: Vec<Value>) -> Result<Value> {
+        let mut registers = arguments;
+        let mut index = start;
+        loop {
+            let Some(instruction) = self.program.instruction(index) else {
+                return self.error("Code runs past the end of the program.".to_string(), index);
+            };
+            let read = |register: usize| registers.get(register).copied().unwrap_or(Value::Void);
+            let value = match instruction {
+                Instruction::Integer(..) => Value::Integer(1),
+                Instruction::Float(..) => Value::Float(1.0),
+                Instruction::Boolean(..) => Value::Boolean(true),
+                Instruction::Void(_) => Value::Void,
+                Instruction::Move(_, src) => read(src as usize),
+                Instruction::Binary(op, _, left, right) => {
+                    self.binary(index, op, read(left as usize), read(right as usize))?
+                }
+                Instruction::BinaryConst(op, _, left, _) => {
+                    self.binary(index, op, read(left as usize), Value::Integer(1))?
+                }
+                Instruction::Global(_, slot) => {
+                    match self.globals.get(slot as usize).copied().flatten() {
+                        Some(value) => value,
+                        None => {
+                            return self
+                                .error("Variable is used before it is set.".to_string(), index)
+                        }
+                    }
+                }
+                Instruction::SetGlobal(slot, src) => {
+                    let slot = slot as usize;
+                    if self.globals.len() <= slot {
+                        self.globals.resize(slot + 1, None);
+                    }
+                    let value = read(src as usize);
+                    let previous = self.globals[slot].replace(value);
+                    if previous.map(|previous| previous.type_name()) != Some(value.type_name()) {
+                        self.generation += 1;
+                        self.calls.clear();
+                    }
+                    index += 1;
+                    continue;
+                }
+                Instruction::Call(function, base) => {
+                    let arity = self
+                        .program
+                        .function(function as usize)
+                        .map_or(0, |function| function.arity as usize);
+                    let arguments = (0..arity)
+                        .map(|offset| read(base as usize + offset))
+                        .collect();
+                    self.call(index, function as usize, arguments)?
+                }
+                Instruction::Return(src) | Instruction::End(src) => return Ok(read(src as usize)),
+            };
+            if let Some(dst) = instruction.destination() {
+                let dst = dst as usize;
+                if registers.len() <= dst {
+                    registers.resize(dst + 1, Value::Void);
+                }
+                registers[dst] = value;
+            }
+            index += 1;
+        }
+    }
+}
+
+impl Program {
+    // The type of the value a run would produce, with `globals` as the values of the
+    // globals when it starts.
+    pub(crate) fn result_type(&self, globals: &[Option<Value>]) -> Result<&'static str> {
+        let mut inference = Inference {
+            program: self,
+            globals: globals
+                .iter()
+                .map(|global| global.map(representative))
+                .collect(),
+            active: vec![false; self.functions().len()],
+            calls: HashMap::new(),
+            generation: 0,
+        };
+        let value = inference.region(self.entry(), Vec::new())?;
+        Ok(value.type_name())
+    }
+}
@@ -1,20 +1,99 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+use crate::BinaryOperator;
+
+pub type Register = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Instruction {
-    Integer(i64),
-    Float(f64),
-    Boolean(bool),
-    Addict,
-    Subtract,
-    Multiply,
-    Divide,
-    Modulo,
-    Equals,
-    NotEquals,
-    Less,
-    Greater,
-    LessEquals,
-    GreaterEquals,
-    End,
+    Integer(Register, i64),
+    Float(Register, f64),
+    Boolean(Register, bool),
+    Void(Register),
+    Move(Register, Register),
+    Binary(BinaryOperator, Register, Register, Register),
+    BinaryConst(BinaryOperator, Register, Register, i64),
+    Global(Register, u32),
+    SetGlobal(u32, Register),
+    Call(u32, Register),
+    Return(Register),
+    End(Register),
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Integer(..) => "Integer",
+            Self::Float(..) => "Float",
+            Self::Boolean(..) => "Boolean",
+            Self::Void(_) => "Void",
+            Self::Move(..) => "Move",
+            Self::Binary(op, ..) => op.mnemonic(),
+            Self::BinaryConst(op, ..) => op.const_mnemonic(),
+            Self::Global(..) => "Global",
+            Self::SetGlobal(..) => "SetGlobal",
+            Self::Call(..) => "Call",
+            Self::Return(_) => "Return",
+            Self::End(_) => "End",
+        }
+    }
+
+    pub fn destination(&self) -> Option<Register> {
+        match *self {
+            Self::Integer(dst, _)
+            | Self::Float(dst, _)
+            | Self::Boolean(dst, _)
+            | Self::Void(dst)
+            | Self::Move(dst, _)
+            | Self::Binary(_, dst, _, _)
+            | Self::BinaryConst(_, dst, _, _)
+            | Self::Global(dst, _)
+            | Self::Call(_, dst) => Some(dst),
+            Self::SetGlobal(..) | Self::Return(_) | Self::End(_) => None,
+        }
+    }
+
+    // Registers read by the instruction, the arguments of a call depend on the callee's arity.
+    pub fn sources(&self) -> [Option<Register>; 2] {
+        match *self {
+            Self::Binary(_, _, left, right) => [Some(left), Some(right)],
+            Self::Move(_, src)
+            | Self::BinaryConst(_, _, src, _)
+            | Self::SetGlobal(_, src)
+            | Self::Return(src)
+            | Self::End(src) => [Some(src), None],
+            Self::Integer(..)
+            | Self::Float(..)
+            | Self::Boolean(..)
+            | Self::Void(_)
+            | Self::Global(..)
+            | Self::Call(..) => [None, None],
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = self.mnemonic();
+        match self {
+            Self::Integer(dst, value) => write!(f, "{mnemonic} r{dst}, {value}"),
+            Self::Float(dst, value) => write!(f, "{mnemonic} r{dst}, {value:?}"),
+            Self::Boolean(dst, value) => write!(f, "{mnemonic} r{dst}, {value}"),
+            Self::Move(dst, src) => write!(f, "{mnemonic} r{dst}, r{src}"),
+            Self::Binary(_, dst, left, right) => {
+                write!(f, "{mnemonic} r{dst}, r{left}, r{right}")
+            }
+            Self::BinaryConst(_, dst, left, value) => {
+                write!(f, "{mnemonic} r{dst}, r{left}, {value}")
+            }
+            Self::Global(dst, slot) => write!(f, "{mnemonic} r{dst}, {slot}"),
+            Self::SetGlobal(slot, src) => write!(f, "{mnemonic} {slot}, r{src}"),
+            Self::Call(function, base) => write!(f, "{mnemonic} {function}, r{base}"),
+            Self::Void(register) | Self::Return(register) | Self::End(register) => {
+                write!(f, "{mnemonic} r{register}")
+            }
+        }
+    }
 }
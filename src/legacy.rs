@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    peephole, token::TokenLocation, BinaryOperator, Error, FunctionInfo, Instruction, LineTable,
+    Program, Register, Result, VerifyError,
+};
+
+// Instruction set of formats 0 to 3, where every instruction worked on an operand stack.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub(crate) enum StackInstruction {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Void,
+    Pop,
+    Addict,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equals,
+    NotEquals,
+    Less,
+    Greater,
+    LessEquals,
+    GreaterEquals,
+    Local(u32),
+    Global(u32),
+    SetGlobal(u32),
+    Call(u32),
+    Return,
+    End,
+    AddictConst(i64),
+    SubtractConst(i64),
+    MultiplyConst(i64),
+    DivideConst(i64),
+    ModuloConst(i64),
+    EqualsConst(i64),
+    NotEqualsConst(i64),
+    LessConst(i64),
+    GreaterConst(i64),
+    LessEqualsConst(i64),
+    GreaterEqualsConst(i64),
+}
+
+impl StackInstruction {
+    fn operator(self) -> Option<(BinaryOperator, Option<i64>)> {
+        Some(match self {
+            Self::Addict => (BinaryOperator::Addict, None),
+            Self::Subtract => (BinaryOperator::Subtract, None),
+            Self::Multiply => (BinaryOperator::Multiply, None),
+            Self::Divide => (BinaryOperator::Divide, None),
+            Self::Modulo => (BinaryOperator::Modulo, None),
+            Self::Equals => (BinaryOperator::Equals, None),
+            Self::NotEquals => (BinaryOperator::NotEquals, None),
+            Self::Less => (BinaryOperator::Less, None),
+            Self::Greater => (BinaryOperator::Greater, None),
+            Self::LessEquals => (BinaryOperator::LessEquals, None),
+            Self::GreaterEquals => (BinaryOperator::GreaterEquals, None),
+            Self::AddictConst(value) => (BinaryOperator::Addict, Some(value)),
+            Self::SubtractConst(value) => (BinaryOperator::Subtract, Some(value)),
+            Self::MultiplyConst(value) => (BinaryOperator::Multiply, Some(value)),
+            Self::DivideConst(value) => (BinaryOperator::Divide, Some(value)),
+            Self::ModuloConst(value) => (BinaryOperator::Modulo, Some(value)),
+            Self::EqualsConst(value) => (BinaryOperator::Equals, Some(value)),
+            Self::NotEqualsConst(value) => (BinaryOperator::NotEquals, Some(value)),
+            Self::LessConst(value) => (BinaryOperator::Less, Some(value)),
+            Self::GreaterConst(value) => (BinaryOperator::Greater, Some(value)),
+            Self::LessEqualsConst(value) => (BinaryOperator::LessEquals, Some(value)),
+            Self::GreaterEqualsConst(value) => (BinaryOperator::GreaterEquals, Some(value)),
+            _ => return None,
+        })
+    }
+}
+
+// Instruction set of the 0.1.0 release, in its declaration order so bincode tags match.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub(crate) enum InstructionV0 {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Addict,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equals,
+    NotEquals,
+    Less,
+    Greater,
+    LessEquals,
+    GreaterEquals,
+    End,
+}
+
+impl From<InstructionV0> for StackInstruction {
+    fn from(old: InstructionV0) -> Self {
+        match old {
+            InstructionV0::Integer(value) => Self::Integer(value),
+            InstructionV0::Float(value) => Self::Float(value),
+            InstructionV0::Boolean(value) => Self::Boolean(value),
+            InstructionV0::Addict => Self::Addict,
+            InstructionV0::Subtract => Self::Subtract,
+            InstructionV0::Multiply => Self::Multiply,
+            InstructionV0::Divide => Self::Divide,
+            InstructionV0::Modulo => Self::Modulo,
+            InstructionV0::Equals => Self::Equals,
+            InstructionV0::NotEquals => Self::NotEquals,
+            InstructionV0::Less => Self::Less,
+            InstructionV0::Greater => Self::Greater,
+            InstructionV0::LessEquals => Self::LessEquals,
+            InstructionV0::GreaterEquals => Self::GreaterEquals,
+            InstructionV0::End => Self::End,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LocationV0 {
+    line: u32,
+    column: u32,
+    length: u32,
+}
+
+// Format 0 is what 0.1.0 wrote: no header, a single script and no source offsets.
+#[derive(Deserialize)]
+pub(crate) struct ProgramV0 {
+    version: String,
+    instructions: Box<[InstructionV0]>,
+    locations: HashMap<usize, LocationV0>,
+}
+
+impl From<ProgramV0> for ProgramV3 {
+    fn from(old: ProgramV0) -> Self {
+        Self {
+            version: old.version,
+            entry: 0,
+            instructions: old.instructions.iter().map(|&op| op.into()).collect(),
+            locations: old
+                .locations
+                .into_iter()
+                .map(|(index, location)| {
+                    let location = TokenLocation {
+                        line: location.line,
+                        column: location.column,
+                        length: location.length,
+                        ..TokenLocation::default()
+                    };
+                    (index, location)
+                })
+                .collect(),
+            parents: HashMap::new(),
+            functions: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
+// Format 1 keyed locations by instruction index, formats 2 and 3 used a line table.
+#[derive(Deserialize)]
+pub(crate) struct StackProgram<L> {
+    version: String,
+    entry: usize,
+    instructions: Box<[StackInstruction]>,
+    locations: L,
+    parents: HashMap<usize, usize>,
+    functions: Vec<FunctionInfo>,
+    files: Vec<String>,
+}
+
+pub(crate) type ProgramV1 = StackProgram<HashMap<usize, TokenLocation>>;
+pub(crate) type ProgramV3 = StackProgram<LineTable>;
+
+impl From<ProgramV1> for ProgramV3 {
+    fn from(old: ProgramV1) -> Self {
+        Self {
+            version: old.version,
+            entry: old.entry,
+            instructions: old.instructions,
+            locations: old.locations.into_iter().collect(),
+            parents: old.parents,
+            functions: old.functions,
+            files: old.files,
+        }
+    }
+}
+
+fn underflow<T>(index: usize) -> Result<T> {
+    Err(Error::Verify(Box::new(VerifyError {
+        message: "Stack underflow in stack bytecode".to_string(),
+        index: Some(index),
+    })))
+}
+
+fn register(depth: usize, index: usize) -> Result<Register> {
+    match Register::try_from(depth) {
+        Ok(register) => Ok(register),
+        Err(_) => Err(Error::Verify(Box::new(VerifyError {
+            message: format!("Stack depth {depth} exceeds the limit of {}", Register::MAX),
+            index: Some(index),
+        }))),
+    }
+}
+
+impl ProgramV3 {
+    // Every stack slot becomes the register with the same index in the frame, so the depth
+    // before an instruction tells which registers it works on.
+    pub(crate) fn translate(self) -> Result<Program> {
+        let mut starts = HashMap::from([(self.entry, 0)]);
+        for function in &self.functions {
+            starts.insert(function.entry, function.arity as usize);
+        }
+        let mut groups = Vec::with_capacity(self.instructions.len());
+        let mut depth = 0;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            if let Some(start) = starts.get(&index) {
+                depth = *start;
+            }
+            let top = |count: usize| match depth.checked_sub(count) {
+                Some(slot) => register(slot, index),
+                None => underflow(index),
+            };
+            let (group, next) = match *instruction {
+                StackInstruction::Integer(value) => {
+                    (vec![Instruction::Integer(top(0)?, value)], depth + 1)
+                }
+                StackInstruction::Float(value) => {
+                    (vec![Instruction::Float(top(0)?, value)], depth + 1)
+                }
+                StackInstruction::Boolean(value) => {
+                    (vec![Instruction::Boolean(top(0)?, value)], depth + 1)
+                }
+                StackInstruction::Void => (vec![Instruction::Void(top(0)?)], depth + 1),
+                StackInstruction::Pop => (Vec::new(), top(1)? as usize),
+                StackInstruction::Local(slot) => (
+                    vec![Instruction::Move(top(0)?, register(slot as usize, index)?)],
+                    depth + 1,
+                ),
+                StackInstruction::Global(slot) => {
+                    (vec![Instruction::Global(top(0)?, slot)], depth + 1)
+                }
+                StackInstruction::SetGlobal(slot) => {
+                    let src = top(1)?;
+                    (vec![Instruction::SetGlobal(slot, src)], depth - 1)
+                }
+                StackInstruction::Call(function) => {
+                    let arity = self
+                        .functions
+                        .get(function as usize)
+                        .map_or(0, |info| info.arity as usize);
+                    let base = top(arity)?;
+                    (vec![Instruction::Call(function, base)], base as usize + 1)
+                }
+                StackInstruction::Return => (vec![Instruction::Return(top(1)?)], 0),
+                StackInstruction::End if depth == 0 => {
+                    (vec![Instruction::Void(0), Instruction::End(0)], 0)
+                }
+                StackInstruction::End => (vec![Instruction::End(top(1)?)], 0),
+                instruction => match instruction.operator() {
+                    Some((op, Some(value))) => {
+                        let register = top(1)?;
+                        (
+                            vec![Instruction::BinaryConst(op, register, register, value)],
+                            depth,
+                        )
+                    }
+                    Some((op, None)) => {
+                        let (left, right) = (top(2)?, top(1)?);
+                        (vec![Instruction::Binary(op, left, left, right)], depth - 1)
+                    }
+                    None => unreachable!(),
+                },
+            };
+            groups.push(group);
+            depth = next;
+        }
+        let mut program = peephole::rebuild(
+            groups,
+            &self.locations,
+            &self.parents,
+            &self.functions,
+            self.entry,
+        );
+        program.set_header(self.version, self.files);
+        Ok(program)
+    }
+}
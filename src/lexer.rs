@@ -1,9 +1,14 @@
-use crate::{identifiers::Identifiers, token::{Token, TokenLocation}};
+use crate::{
+    identifiers::Identifiers,
+    token::{Token, TokenLocation},
+    FileId,
+};
 
 pub struct Lexer<I> {
     iter: I,
     current: Option<u8>,
     offset: u32,
+    position: u32,
     location: TokenLocation,
     identifiers: Identifiers,
 }
@@ -12,12 +17,16 @@ impl<I> Lexer<I>
 where
     I: Iterator<Item = u8>,
 {
-    pub fn new(mut iter: I) -> Self {
+    pub fn new(mut iter: I, file: FileId) -> Self {
         Self {
             current: iter.next(),
             iter,
             offset: 0,
-            location: TokenLocation::default(),
+            position: 0,
+            location: TokenLocation {
+                file,
+                ..TokenLocation::default()
+            },
             identifiers: Identifiers::new(),
         }
     }
@@ -25,6 +34,7 @@ where
     fn advance(&mut self) {
         self.current = self.iter.next();
         self.offset += 1;
+        self.position += 1;
     }
 
     fn single(&mut self, kind: Token) -> Token {
@@ -68,6 +78,7 @@ where
         while let Some(c) = self.current {
             if c.is_ascii_whitespace() {
                 self.current = self.iter.next();
+                self.position += 1;
                 if c == b'\n' {
                     self.location.line += 1;
                     self.offset = 0;
@@ -134,6 +145,10 @@ where
         let token = match self.identifiers.get(&id) {
             b"true" => Token::True,
             b"false" => Token::False,
+            b"fn" => Token::Fn,
+            b"import" => Token::Import,
+            b"as" => Token::As,
+            b"let" => Token::Let,
             _ => {
                 remove = false;
                 Token::Identifier(id)
@@ -145,9 +160,30 @@ where
         token
     }
 
+    fn string(&mut self) -> Token {
+        self.advance();
+        let start = self.identifiers.start();
+        while let Some(c) = self.current {
+            match c {
+                b'"' => {
+                    self.advance();
+                    return Token::String(self.identifiers.finish(start));
+                }
+                b'\n' => break,
+                _ => {
+                    self.identifiers.push(c);
+                    self.advance();
+                }
+            }
+        }
+        self.identifiers.restart(start);
+        Token::UnterminatedString
+    }
+
     pub fn next(&mut self) -> Token {
         self.whitespaces();
         self.location.column = self.offset;
+        self.location.offset = self.position;
         let token = if let Some(c) = self.current {
             match c {
                 b'+' => self.single(Token::Plus),
@@ -155,6 +191,14 @@ where
                 b'*' => self.single(Token::Asterisk),
                 b'/' => self.single(Token::Slash),
                 b'%' => self.single(Token::Percent),
+                b';' => self.single(Token::Semicolon),
+                b',' => self.single(Token::Comma),
+                b'.' => self.single(Token::Dot),
+                b'(' => self.single(Token::LeftParen),
+                b')' => self.single(Token::RightParen),
+                b'{' => self.single(Token::LeftBrace),
+                b'}' => self.single(Token::RightBrace),
+                b'"' => self.string(),
                 b'!' => self.exclamation(),
                 b'<' => self.less(),
                 b'>' => self.greater(),
@@ -1,82 +1,127 @@
+use std::io::{self, IsTerminal};
+
+mod assembler;
+mod cancel;
+mod compile_options;
 mod compiler;
+mod debugger;
+mod diagnostic;
+mod disassembler;
+mod error;
+mod format;
+mod identifiers;
+mod inference;
 mod instruction;
+mod legacy;
 mod lexer;
+mod line_table;
+mod module;
 mod node;
 mod parser;
+mod peephole;
 mod program;
+mod repl;
 mod source_error;
+mod source_map;
 mod state;
+mod state_options;
 mod token;
 mod value;
+mod verifier;
 mod vm_error;
-mod identifiers;
+mod word;
 
+pub use cancel::*;
+pub use compile_options::*;
+pub use debugger::*;
+pub use diagnostic::*;
+pub use error::*;
+pub use format::*;
 pub use instruction::*;
+pub use line_table::*;
+pub use module::*;
 pub use node::*;
 pub use program::*;
+pub use repl::*;
 pub use source_error::*;
+pub use source_map::*;
 pub use state::*;
-use token::TokenLocation;
+pub use state_options::*;
+pub use token::TokenLocation;
 pub use value::*;
+pub use verifier::*;
 pub use vm_error::*;
 
-pub fn print_error(message: String, location: TokenLocation, file: &str, source: &str) {
-    println!("In file: {file}, line {}", (location.line + 1));
-    println!("{}", message);
-    if let Some(line) = source.lines().skip(location.line as usize).next() {
-        println!("{line}");
-        for _ in 0..location.column {
-            print!(" ");
-        }
-        for _ in 0..location.length {
-            print!("^");
-        }
-        println!();
+fn report_error(error: &Error, sources: &SourceMap) {
+    let stderr = io::stderr();
+    let renderer = Renderer::new().with_color(stderr.is_terminal());
+    let _ = renderer.render(&mut stderr.lock(), &error.diagnostic(), sources);
+}
+
+fn source(sources: &SourceMap, file: FileId) -> Result<&str> {
+    match sources.get(file) {
+        Some(file) => Ok(file.source()),
+        None => Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            "file is not part of the source map",
+        ))),
     }
 }
 
-pub fn parse(code: &[u8]) -> SourceResult<Option<Node>> {
-    parser::Parser::new(code.iter().copied()).parse()
+pub fn parse(code: &[u8]) -> Result<Option<Node>> {
+    parser::Parser::new(code.iter().copied(), FileId::default()).parse()
+}
+
+pub fn parse_recovering(code: &[u8]) -> (Option<Node>, Vec<Diagnostic>) {
+    let mut parser = parser::Parser::new(code.iter().copied(), FileId::default());
+    let (ast, errors) = parser.parse_recovering();
+    (ast, errors.iter().map(Error::diagnostic).collect())
+}
+
+pub fn parse_file(sources: &SourceMap, file: FileId) -> Result<Option<Node>> {
+    parser::Parser::new(source(sources, file)?.bytes(), file).parse()
 }
 
-pub fn compile(ast: &Option<Node>) -> SourceResult<Program> {
-    let mut compiler = compiler::Compiler::new();
-    compiler.compile(ast)?;
+pub fn parse_file_recovering(
+    sources: &SourceMap,
+    file: FileId,
+) -> Result<(Option<Node>, Vec<Diagnostic>)> {
+    let mut parser = parser::Parser::new(source(sources, file)?.bytes(), file);
+    let (ast, errors) = parser.parse_recovering();
+    Ok((ast, errors.iter().map(Error::diagnostic).collect()))
+}
+
+pub fn compile(ast: &Option<Node>) -> Result<Program> {
+    compile_with_options(ast, CompileOptions::default())
+}
+
+pub fn compile_with_options(ast: &Option<Node>, options: CompileOptions) -> Result<Program> {
+    let mut compiler = compiler::Compiler::with_options(options);
+    compiler.compile(ast, None)?;
     Ok(compiler.finish())
 }
 
-pub fn parse_and_compile(code: &[u8]) -> SourceResult<Program> {
+pub fn parse_and_compile(code: &[u8]) -> Result<Program> {
     compile(&parse(code)?)
 }
 
-pub fn run(program: &Program) -> SourceResult<Value> {
-    let mut state = State::new();
-    state.run(program).map_err(|error| {
-        let location = program.location(state.program_counter()).unwrap();
-        Box::new(SourceError {
-            message: error.to_string(),
-            location,
-        })
-    })
+pub fn compile_file(sources: &SourceMap, file: FileId) -> Result<Program> {
+    let mut program = compile(&parse_file(sources, file)?)?;
+    program.set_files(sources);
+    Ok(program)
+}
+
+pub fn run(program: &Program) -> Result<Value> {
+    State::new().execute(program)
 }
 
 pub fn eval(code: &str) -> Value {
-    let program = parse_and_compile(code.as_bytes()).unwrap_or_else(|error| {
-        print_error(
-            format!("Compilation error: {}", error.message),
-            error.location,
-            "user code",
-            code,
-        );
-        std::process::exit(1);
-    });
-    run(&program).unwrap_or_else(|error| {
-        print_error(
-            format!("Runtime error: {}", error.message),
-            error.location,
-            "user code",
-            code,
-        );
-        std::process::exit(1);
-    })
+    let mut sources = SourceMap::new();
+    let file = sources.add("user code".to_string(), code.to_string());
+    compile_file(&sources, file)
+        .and_then(|program| run(&program))
+        .unwrap_or_else(|error| {
+            report_error(&error, &sources);
+            std::process::exit(1);
+        })
 }
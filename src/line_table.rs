@@ -0,0 +1,168 @@
+use std::fmt;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeSeq,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{token::TokenLocation, FileId};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineTable {
+    entries: Vec<(usize, TokenLocation)>,
+}
+
+impl LineTable {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, index: usize, location: TokenLocation) {
+        if self.entries.last().is_some_and(|(last, _)| *last == index) {
+            self.entries.pop();
+        }
+        match self.entries.last() {
+            Some((_, last)) if *last == location => {}
+            _ => self.entries.push((index, location)),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<TokenLocation> {
+        let position = self.entries.partition_point(|(start, _)| *start <= index);
+        position
+            .checked_sub(1)
+            .map(|position| self.entries[position].1)
+    }
+
+    pub fn starts_at(&self, index: usize) -> Option<TokenLocation> {
+        self.entries
+            .binary_search_by_key(&index, |(start, _)| *start)
+            .ok()
+            .map(|position| self.entries[position].1)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (usize, TokenLocation)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Drops the locations of instructions from `length` on.
+    pub(crate) fn truncate(&mut self, length: usize) {
+        let keep = self.entries.partition_point(|(start, _)| *start < length);
+        self.entries.truncate(keep);
+    }
+
+    pub(crate) fn is_sorted(&self) -> bool {
+        self.entries.windows(2).all(|pair| pair[0].0 < pair[1].0)
+    }
+}
+
+impl FromIterator<(usize, TokenLocation)> for LineTable {
+    fn from_iter<T: IntoIterator<Item = (usize, TokenLocation)>>(iter: T) -> Self {
+        let mut entries: Vec<_> = iter.into_iter().collect();
+        entries.sort_by_key(|(index, _)| *index);
+        let mut table = Self::new();
+        for (index, location) in entries {
+            table.push(index, location);
+        }
+        table
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    index: usize,
+    #[serde(flatten)]
+    location: TokenLocation,
+}
+
+// Binary entries store every field as a difference to the previous entry.
+#[derive(Serialize, Deserialize)]
+struct Delta {
+    index: u32,
+    file: u32,
+    line: i32,
+    column: u32,
+    offset: i32,
+    length: u32,
+}
+
+impl Serialize for LineTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let human_readable = serializer.is_human_readable();
+        let mut seq = serializer.serialize_seq(Some(self.entries.len()))?;
+        let mut previous = (0, TokenLocation::default());
+        for (index, location) in self.entries() {
+            if human_readable {
+                seq.serialize_element(&Entry { index, location })?;
+                continue;
+            }
+            seq.serialize_element(&Delta {
+                index: index.wrapping_sub(previous.0) as u32,
+                file: location.file.index() as u32,
+                line: location.line.wrapping_sub(previous.1.line) as i32,
+                column: location.column,
+                offset: location.offset.wrapping_sub(previous.1.offset) as i32,
+                length: location.length,
+            })?;
+            previous = (index, location);
+        }
+        seq.end()
+    }
+}
+
+struct TableVisitor {
+    human_readable: bool,
+}
+
+impl<'de> Visitor<'de> for TableVisitor {
+    type Value = LineTable;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a line table")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<LineTable, A::Error> {
+        let mut entries = Vec::new();
+        if self.human_readable {
+            while let Some(Entry { index, location }) = seq.next_element()? {
+                entries.push((index, location));
+            }
+        } else {
+            let mut previous = (0usize, TokenLocation::default());
+            while let Some(delta) = seq.next_element::<Delta>()? {
+                let location = TokenLocation {
+                    file: FileId::new(delta.file),
+                    offset: previous.1.offset.wrapping_add(delta.offset as u32),
+                    line: previous.1.line.wrapping_add(delta.line as u32),
+                    column: delta.column,
+                    length: delta.length,
+                };
+                let index = previous
+                    .0
+                    .checked_add(delta.index as usize)
+                    .ok_or_else(|| de::Error::custom("line table index overflow"))?;
+                entries.push((index, location));
+                previous = (index, location);
+            }
+        }
+        Ok(LineTable { entries })
+    }
+}
+
+impl<'de> Deserialize<'de> for LineTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_seq(TableVisitor { human_readable })
+    }
+}
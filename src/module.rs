@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    compiler::Compiler, parse_file, token::TokenLocation, CompileOptions, Error, FileId,
+    FunctionInfo, Instruction, LineTable, Node, Program, Result, SourceError, SourceMap,
+};
+
+fn normalize(path: &Path) -> String {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            component => result.push(component),
+        }
+    }
+    result.to_string_lossy().replace('\\', "/")
+}
+
+pub trait ModuleLoader {
+    fn load(&self, path: &str) -> io::Result<String>;
+
+    fn resolve(&self, importer: &str, path: &str) -> String {
+        let directory = Path::new(importer).parent().unwrap_or(Path::new(""));
+        normalize(&directory.join(path))
+    }
+}
+
+pub struct FileSystemLoader;
+
+impl ModuleLoader for FileSystemLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, path: &str, source: &str) {
+        self.files
+            .insert(normalize(Path::new(path)), source.to_string());
+    }
+
+    pub fn with(mut self, path: &str, source: &str) -> Self {
+        self.insert(path, source);
+        self
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        match self.files.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no module named '{path}'"),
+            )),
+        }
+    }
+}
+
+pub(crate) struct Module {
+    pub instructions: Vec<Instruction>,
+    pub locations: LineTable,
+    pub parents: HashMap<usize, usize>,
+    pub functions: Vec<FunctionInfo>,
+    pub parameter_names: Vec<Vec<String>>,
+    pub exports: HashMap<String, usize>,
+}
+
+pub(crate) struct Modules {
+    loader: Box<dyn ModuleLoader>,
+    sources: SourceMap,
+    cache: HashMap<String, Rc<Module>>,
+    loading: Vec<String>,
+    options: CompileOptions,
+}
+
+fn compile_error<T>(message: String, location: TokenLocation) -> Result<T> {
+    Err(Error::Compile(Box::new(SourceError { message, location })))
+}
+
+impl Modules {
+    pub fn new(loader: Box<dyn ModuleLoader>) -> Self {
+        Self {
+            loader,
+            sources: SourceMap::new(),
+            cache: HashMap::new(),
+            loading: Vec::new(),
+            options: CompileOptions::default(),
+        }
+    }
+
+    pub fn options(&self) -> CompileOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: CompileOptions) {
+        if options != self.options {
+            self.cache.clear();
+        }
+        self.options = options;
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    pub fn import(&mut self, path: &str, location: TokenLocation) -> Result<Rc<Module>> {
+        let importer = self
+            .sources
+            .get(location.file)
+            .map_or("", |file| file.name());
+        let key = self.loader.resolve(importer, path);
+        if let Some(module) = self.cache.get(&key) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|loading| *loading == key) {
+            let mut cycle = self.loading[start..].to_vec();
+            cycle.push(key);
+            return compile_error(
+                format!("Import cycle detected: {}.", cycle.join(" -> ")),
+                location,
+            );
+        }
+        let source = match self.loader.load(&key) {
+            Ok(source) => source,
+            Err(error) => {
+                return compile_error(format!("Unable to load module '{key}': {error}."), location)
+            }
+        };
+        let file = self.sources.add(key.clone(), source);
+        self.loading.push(key.clone());
+        let result = parse_file(&self.sources, file).and_then(|ast| {
+            let mut compiler = Compiler::with_options(self.options);
+            compiler.compile_module(&ast, file, Some(self))?;
+            Ok(compiler.finish_module())
+        });
+        self.loading.pop();
+        let module = Rc::new(result?);
+        self.cache.insert(key, module.clone());
+        Ok(module)
+    }
+
+    fn compile(
+        &mut self,
+        compiler: &mut Compiler,
+        file: FileId,
+        compile: fn(&mut Compiler, &Option<Node>, Option<&mut Modules>) -> Result<()>,
+    ) -> Result<()> {
+        let name = self.sources.get(file).map_or("", |file| file.name());
+        self.loading.push(self.loader.resolve("", name));
+        let result =
+            parse_file(&self.sources, file).and_then(|ast| compile(compiler, &ast, Some(self)));
+        self.loading.pop();
+        result
+    }
+
+    fn build(&mut self, file: FileId) -> Result<Program> {
+        let mut compiler = Compiler::with_options(self.options);
+        self.compile(&mut compiler, file, Compiler::compile)?;
+        let mut program = compiler.finish();
+        program.set_files(&self.sources);
+        Ok(program)
+    }
+
+    pub fn compile_source(&mut self, name: &str, source: &str) -> Result<Program> {
+        let file = self.sources.add(name.to_string(), source.to_string());
+        self.build(file)
+    }
+
+    pub fn compile_path(&mut self, path: &str) -> Result<Program> {
+        let key = self.loader.resolve("", path);
+        let source = self.loader.load(&key)?;
+        let file = self.sources.add(key, source);
+        self.build(file)
+    }
+
+    pub fn add_source(&mut self, name: &str, source: &str) -> FileId {
+        self.sources.add(name.to_string(), source.to_string())
+    }
+
+    pub fn replace_source(&mut self, file: FileId, name: &str, source: &str) {
+        self.sources
+            .replace(file, name.to_string(), source.to_string());
+    }
+
+    // Compiles `file` with a compiler that stays in use afterwards, like a debugger's.
+    pub fn compile_with(&mut self, compiler: &mut Compiler, file: FileId) -> Result<Program> {
+        self.compile(compiler, file, Compiler::compile)?;
+        let mut program = compiler.program();
+        program.set_files(&self.sources);
+        Ok(program)
+    }
+
+    // Compiles `file` as the next input of a session that keeps using `compiler`.
+    pub fn compile_input(&mut self, compiler: &mut Compiler, file: FileId) -> Result<Program> {
+        self.compile(compiler, file, Compiler::compile_input)?;
+        let mut program = compiler.program();
+        program.set_files(&self.sources);
+        Ok(program)
+    }
+}
@@ -1,9 +1,45 @@
-use crate::{token::TokenLocation, Instruction};
+use crate::{token::TokenLocation, BinaryOperator};
 
 pub struct Binary {
     pub left: Node,
     pub right: Node,
-    pub instruction: Instruction,
+    pub operator: BinaryOperator,
+    pub location: TokenLocation,
+}
+
+pub struct Block {
+    pub statements: Vec<Node>,
+    pub result: Option<Node>,
+}
+
+pub struct Identifier {
+    pub name: String,
+    pub location: TokenLocation,
+}
+
+pub struct Call {
+    pub module: Option<String>,
+    pub name: String,
+    pub arguments: Vec<Node>,
+    pub location: TokenLocation,
+}
+
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Option<Node>,
+    pub location: TokenLocation,
+}
+
+pub struct Let {
+    pub name: String,
+    pub value: Node,
+    pub location: TokenLocation,
+}
+
+pub struct Import {
+    pub path: String,
+    pub alias: String,
     pub location: TokenLocation,
 }
 
@@ -12,6 +48,12 @@ pub enum Node {
     Integer(i64),
     Float(f64),
     Binary(Box<Binary>),
+    Block(Box<Block>),
+    Identifier(Box<Identifier>),
+    Call(Box<Call>),
+    Function(Box<Function>),
+    Let(Box<Let>),
+    Import(Box<Import>),
 }
 
 impl Node {
@@ -30,14 +72,70 @@ impl Node {
     pub fn new_binary(
         left: Self,
         right: Self,
-        instruction: Instruction,
+        operator: BinaryOperator,
         location: TokenLocation,
     ) -> Self {
         Self::Binary(Box::new(Binary {
             left,
             right,
-            instruction,
+            operator,
             location,
         }))
     }
+
+    pub fn new_block(statements: Vec<Self>, result: Option<Self>) -> Self {
+        Self::Block(Box::new(Block { statements, result }))
+    }
+
+    pub fn new_identifier(name: String, location: TokenLocation) -> Self {
+        Self::Identifier(Box::new(Identifier { name, location }))
+    }
+
+    pub fn new_call(
+        module: Option<String>,
+        name: String,
+        arguments: Vec<Self>,
+        location: TokenLocation,
+    ) -> Self {
+        Self::Call(Box::new(Call {
+            module,
+            name,
+            arguments,
+            location,
+        }))
+    }
+
+    pub fn new_function(
+        name: String,
+        parameters: Vec<String>,
+        body: Option<Self>,
+        location: TokenLocation,
+    ) -> Self {
+        Self::Function(Box::new(Function {
+            name,
+            parameters,
+            body,
+            location,
+        }))
+    }
+
+    pub fn new_let(name: String, value: Self, location: TokenLocation) -> Self {
+        Self::Let(Box::new(Let {
+            name,
+            value,
+            location,
+        }))
+    }
+
+    pub fn new_import(path: String, alias: String, location: TokenLocation) -> Self {
+        Self::Import(Box::new(Import {
+            path,
+            alias,
+            location,
+        }))
+    }
+
+    pub fn is_item(&self) -> bool {
+        matches!(self, Self::Function(_) | Self::Import(_))
+    }
 }
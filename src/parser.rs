@@ -1,8 +1,7 @@
 use crate::{
     lexer::Lexer,
-    source_error,
     token::{Token, TokenWriter},
-    Instruction, Node, SourceResult,
+    BinaryOperator, Error, FileId, Node, Result, SourceError,
 };
 
 #[derive(PartialEq, PartialOrd, Clone, Copy)]
@@ -24,19 +23,19 @@ impl Precedence {
     }
 }
 
-fn precedence_and_instruction_from_token(token: &Token) -> Option<(Precedence, Instruction)> {
+fn precedence_and_operator_from_token(token: &Token) -> Option<(Precedence, BinaryOperator)> {
     match token {
-        Token::Plus => Some((Precedence::Term, Instruction::Addict)),
-        Token::Minus => Some((Precedence::Term, Instruction::Subtract)),
-        Token::Asterisk => Some((Precedence::Factor, Instruction::Multiply)),
-        Token::Slash => Some((Precedence::Factor, Instruction::Divide)),
-        Token::Percent => Some((Precedence::Factor, Instruction::Modulo)),
-        Token::EqualsEquals => Some((Precedence::Comparison, Instruction::Equals)),
-        Token::ExclamationEquals => Some((Precedence::Comparison, Instruction::NotEquals)),
-        Token::Less => Some((Precedence::Comparison, Instruction::Less)),
-        Token::Greater => Some((Precedence::Comparison, Instruction::Greater)),
-        Token::LessEquals => Some((Precedence::Comparison, Instruction::LessEquals)),
-        Token::GreaterEquals => Some((Precedence::Comparison, Instruction::GreaterEquals)),
+        Token::Plus => Some((Precedence::Term, BinaryOperator::Addict)),
+        Token::Minus => Some((Precedence::Term, BinaryOperator::Subtract)),
+        Token::Asterisk => Some((Precedence::Factor, BinaryOperator::Multiply)),
+        Token::Slash => Some((Precedence::Factor, BinaryOperator::Divide)),
+        Token::Percent => Some((Precedence::Factor, BinaryOperator::Modulo)),
+        Token::EqualsEquals => Some((Precedence::Comparison, BinaryOperator::Equals)),
+        Token::ExclamationEquals => Some((Precedence::Comparison, BinaryOperator::NotEquals)),
+        Token::Less => Some((Precedence::Comparison, BinaryOperator::Less)),
+        Token::Greater => Some((Precedence::Comparison, BinaryOperator::Greater)),
+        Token::LessEquals => Some((Precedence::Comparison, BinaryOperator::LessEquals)),
+        Token::GreaterEquals => Some((Precedence::Comparison, BinaryOperator::GreaterEquals)),
         _ => None,
     }
 }
@@ -44,17 +43,19 @@ fn precedence_and_instruction_from_token(token: &Token) -> Option<(Precedence, I
 pub struct Parser<I> {
     lexer: Lexer<I>,
     token: Token,
+    errors: Vec<Error>,
 }
 
 impl<I> Parser<I>
 where
     I: Iterator<Item = u8>,
 {
-    pub fn new(iter: I) -> Self {
-        let mut lexer = Lexer::new(iter);
+    pub fn new(iter: I, file: FileId) -> Self {
+        let mut lexer = Lexer::new(iter, file);
         Self {
             token: lexer.next(),
             lexer,
+            errors: Vec::new(),
         }
     }
 
@@ -62,38 +63,120 @@ where
         self.token = self.lexer.next();
     }
 
-    fn error<T>(&self, message: String) -> SourceResult<T> {
-        source_error(message, self.lexer.location())
+    fn source_error(&self, message: String) -> Box<SourceError> {
+        Box::new(SourceError {
+            message,
+            location: self.lexer.location(),
+        })
     }
 
-    fn primary(&mut self) -> SourceResult<Node> {
-        let result = match &self.token {
-            Token::True => Node::new_boolean(true),
-            Token::False => Node::new_boolean(false),
-            Token::Integer(value) => Node::new_integer(*value),
-            Token::Float(value) => Node::new_float(*value),
-            Token::Unknown(c) => self.error(format!(
-                "Expected value, found unknown character '{}'.",
+    fn error<T>(&self, message: String) -> Result<T> {
+        Err(Error::Parse(self.source_error(message)))
+    }
+
+    fn unexpected_error(&self, expected: &str) -> Error {
+        match &self.token {
+            Token::Unknown(c) => Error::Lex(self.source_error(format!(
+                "Expected {expected}, found unknown character '{}'.",
                 *c as char
-            ))?,
-            Token::End => self.error(format!("Expected value, found end."))?,
-            Token::ToBigInteger => self.error(format!(
+            ))),
+            Token::ToBigInteger => Error::Lex(self.source_error(format!(
                 "Integer to big, supported range is from {} to {}",
                 i64::MIN,
                 i64::MAX
-            ))?,
+            ))),
+            Token::UnterminatedString => Error::Lex(
+                self.source_error("Unterminated string, expected closing '\"'.".to_string()),
+            ),
+            Token::End => {
+                Error::Parse(self.source_error(format!("Expected {expected}, found end.")))
+            }
             token => {
                 let writer = TokenWriter::new(token, self.lexer.identifiers());
-                self.error(format!("Expected value, found '{writer}'."))?
+                Error::Parse(self.source_error(format!("Expected {expected}, found '{writer}'.")))
+            }
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T> {
+        Err(self.unexpected_error(expected))
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<()> {
+        if self.token == token {
+            self.advance();
+            Ok(())
+        } else {
+            self.unexpected(expected)
+        }
+    }
+
+    fn identifier(&mut self, expected: &str) -> Result<String> {
+        match &self.token {
+            Token::Identifier(id) => {
+                let name = String::from_utf8_lossy(self.lexer.identifiers().get(id)).into_owned();
+                self.advance();
+                Ok(name)
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Node>> {
+        self.expect(Token::LeftParen, "'('")?;
+        let mut arguments = Vec::new();
+        while self.token != Token::RightParen {
+            arguments.push(self.expression()?);
+            if self.token != Token::Comma {
+                break;
             }
+            self.advance();
+        }
+        self.expect(Token::RightParen, "',' or ')'")?;
+        Ok(arguments)
+    }
+
+    fn named(&mut self) -> Result<Node> {
+        let location = self.lexer.location();
+        let name = self.identifier("value")?;
+        match self.token {
+            Token::Dot => {
+                self.advance();
+                let location = self.lexer.location();
+                let function = self.identifier("function name")?;
+                let arguments = self.arguments()?;
+                Ok(Node::new_call(Some(name), function, arguments, location))
+            }
+            Token::LeftParen => {
+                let arguments = self.arguments()?;
+                Ok(Node::new_call(None, name, arguments, location))
+            }
+            _ => Ok(Node::new_identifier(name, location)),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let result = match &self.token {
+            Token::True => Node::new_boolean(true),
+            Token::False => Node::new_boolean(false),
+            Token::Integer(value) => Node::new_integer(*value),
+            Token::Float(value) => Node::new_float(*value),
+            Token::Identifier(_) => return self.named(),
+            Token::LeftParen => {
+                self.advance();
+                let node = self.expression()?;
+                self.expect(Token::RightParen, "')'")?;
+                return Ok(node);
+            }
+            _ => self.unexpected("value")?,
         };
         self.advance();
         Ok(result)
     }
 
-    fn binary(&mut self, expression_precedence: Precedence, mut left: Node) -> SourceResult<Node> {
-        while let Some((token_precedence, instruction)) =
-            precedence_and_instruction_from_token(&self.token)
+    fn binary(&mut self, expression_precedence: Precedence, mut left: Node) -> Result<Node> {
+        while let Some((token_precedence, operator)) =
+            precedence_and_operator_from_token(&self.token)
         {
             if token_precedence < expression_precedence {
                 break;
@@ -101,42 +184,169 @@ where
             let location = self.lexer.location();
             self.advance();
             let mut right = self.primary()?;
-            if let Some((next_precedence, _)) = precedence_and_instruction_from_token(&self.token) {
+            if let Some((next_precedence, _)) = precedence_and_operator_from_token(&self.token) {
                 if token_precedence < next_precedence {
                     right = self.binary(token_precedence.next(), right)?;
                 }
             }
-            left = Node::new_binary(left, right, instruction, location);
+            left = Node::new_binary(left, right, operator, location);
         }
         Ok(left)
     }
 
-    fn expression(&mut self) -> SourceResult<Node> {
+    fn expression(&mut self) -> Result<Node> {
         let left = self.primary()?;
         self.binary(Precedence::None, left)
     }
 
-    pub fn parse(&mut self) -> SourceResult<Option<Node>> {
-        if self.token == Token::End {
-            Ok(None)
+    fn variable(&mut self) -> Result<Node> {
+        let location = self.lexer.location();
+        self.advance();
+        let name = self.identifier("variable name")?;
+        self.expect(Token::Equals, "'='")?;
+        let value = self.expression()?;
+        Ok(Node::new_let(name, value, location))
+    }
+
+    fn import(&mut self) -> Result<Node> {
+        let location = self.lexer.location();
+        self.advance();
+        let path = match &self.token {
+            Token::String(id) => {
+                String::from_utf8_lossy(self.lexer.identifiers().get(id)).into_owned()
+            }
+            _ => self.unexpected("module path")?,
+        };
+        self.advance();
+        self.expect(Token::As, "'as'")?;
+        let alias = self.identifier("module name")?;
+        Ok(Node::new_import(path, alias, location))
+    }
+
+    fn function(&mut self) -> Result<Node> {
+        let location = self.lexer.location();
+        self.advance();
+        let name = self.identifier("function name")?;
+        self.expect(Token::LeftParen, "'('")?;
+        let mut parameters = Vec::new();
+        while self.token != Token::RightParen {
+            parameters.push(self.identifier("parameter name")?);
+            if self.token != Token::Comma {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(Token::RightParen, "',' or ')'")?;
+        self.expect(Token::LeftBrace, "'{'")?;
+        let body = self.block(Token::RightBrace, false);
+        self.expect(Token::RightBrace, "'}'")?;
+        Ok(Node::new_function(name, parameters, body, location))
+    }
+
+    fn statement_end(&mut self, closing: &Token) -> Result<bool> {
+        if self.token == Token::Semicolon {
+            self.advance();
+            Ok(true)
+        } else if self.token == *closing {
+            Ok(false)
+        } else if *closing == Token::End {
+            self.unexpected("';' or end")
         } else {
-            let node = self.expression()?;
-            match &self.token {
-                Token::Unknown(c) => self.error(format!(
-                    "Expected end, found unknown character '{}'.",
-                    *c as char
-                ))?,
-                Token::ToBigInteger => self.error(format!(
-                    "Integer to big, supported range is from {} to {}",
-                    i64::MIN,
-                    i64::MAX
-                ))?,
-                Token::End => Ok(Some(node)),
-                token => {
-                    let writer = TokenWriter::new(token, self.lexer.identifiers());
-                    self.error(format!("Expected end, found '{writer}'"))?
+            self.unexpected("';' or '}'")
+        }
+    }
+
+    fn statement(&mut self, closing: &Token, top_level: bool) -> Result<(Node, bool)> {
+        match self.token {
+            Token::Import | Token::Fn if !top_level => {
+                self.error("Imports and functions are only allowed at the top level.".to_string())
+            }
+            Token::Let if !top_level => {
+                self.error("Variables are only allowed at the top level.".to_string())
+            }
+            Token::Let => {
+                let node = self.variable()?;
+                let terminated = self.statement_end(closing)?;
+                Ok((node, terminated))
+            }
+            Token::Import => {
+                let node = self.import()?;
+                self.statement_end(closing)?;
+                Ok((node, true))
+            }
+            Token::Fn => {
+                let node = self.function()?;
+                if self.token == Token::Semicolon {
+                    self.advance();
                 }
+                Ok((node, true))
             }
+            _ => {
+                let node = self.expression()?;
+                let terminated = self.statement_end(closing)?;
+                Ok((node, terminated))
+            }
+        }
+    }
+
+    fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.token {
+                Token::End => break,
+                Token::Semicolon if depth == 0 => {
+                    self.advance();
+                    break;
+                }
+                Token::RightBrace if depth == 0 => break,
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    fn block(&mut self, closing: Token, top_level: bool) -> Option<Node> {
+        let mut statements = Vec::new();
+        let mut result = None;
+        let mut separated = false;
+        while self.token != closing && self.token != Token::End {
+            if self.token == Token::RightBrace {
+                self.errors.push(self.unexpected_error("statement"));
+                self.advance();
+                continue;
+            }
+            match self.statement(&closing, top_level) {
+                Ok((node, true)) => {
+                    statements.push(node);
+                    separated = true;
+                }
+                Ok((node, false)) => result = Some(node),
+                Err(error) => {
+                    self.errors.push(error);
+                    separated = true;
+                    self.synchronize();
+                }
+            }
+        }
+        if separated {
+            Some(Node::new_block(statements, result))
+        } else {
+            result
+        }
+    }
+
+    pub fn parse_recovering(&mut self) -> (Option<Node>, Vec<Error>) {
+        let ast = self.block(Token::End, true);
+        (ast, std::mem::take(&mut self.errors))
+    }
+
+    pub fn parse(&mut self) -> Result<Option<Node>> {
+        let (ast, errors) = self.parse_recovering();
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(ast),
         }
     }
 }
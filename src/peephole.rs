@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use crate::{FunctionInfo, Instruction, LineTable, Program, Register};
+
+// Builds a program where every old instruction is replaced by its group of new instructions.
+// New instructions keep the location of the instruction they replace, removed instructions
+// hand their role as jump target over to the next surviving one.
+pub(crate) fn rebuild(
+    groups: Vec<Vec<Instruction>>,
+    locations: &LineTable,
+    parents: &HashMap<usize, usize>,
+    functions: &[FunctionInfo],
+    entry: usize,
+) -> Program {
+    let mut instructions = Vec::with_capacity(groups.len());
+    let mut table = LineTable::new();
+    let mut starts = Vec::with_capacity(groups.len() + 1);
+    let mut lasts = Vec::with_capacity(groups.len());
+    for (index, group) in groups.into_iter().enumerate() {
+        starts.push(instructions.len());
+        lasts.push((!group.is_empty()).then(|| instructions.len() + group.len() - 1));
+        if !group.is_empty() {
+            if let Some(location) = locations.get(index) {
+                table.push(instructions.len(), location);
+            }
+        }
+        instructions.extend(group);
+    }
+    starts.push(instructions.len());
+    for index in (0..lasts.len()).rev() {
+        if lasts[index].is_none() {
+            starts[index] = starts[index + 1];
+        }
+    }
+
+    let length = instructions.len();
+    let parents = parents
+        .iter()
+        .filter_map(|(child, parent)| {
+            let child = (*lasts.get(*child)?)?;
+            let parent = lasts
+                .get(*parent)
+                .copied()
+                .flatten()
+                .or(starts.get(*parent).copied())?;
+            (parent > child && parent < length).then_some((child, parent))
+        })
+        .collect();
+    let functions = functions
+        .iter()
+        .map(|function| FunctionInfo {
+            entry: starts.get(function.entry).copied().unwrap_or(length),
+            ..function.clone()
+        })
+        .collect();
+    let entry = starts.get(entry).copied().unwrap_or(length);
+    Program::new(
+        instructions.into_boxed_slice(),
+        table,
+        parents,
+        functions,
+        entry,
+    )
+}
+
+fn is_load(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Integer(..)
+            | Instruction::Float(..)
+            | Instruction::Boolean(..)
+            | Instruction::Void(_)
+            | Instruction::Move(..)
+    )
+}
+
+fn reads(program: &Program, instruction: Instruction, register: Register) -> bool {
+    if instruction.sources().contains(&Some(register)) {
+        return true;
+    }
+    match instruction {
+        Instruction::Call(function, base) => {
+            program.function(function as usize).is_some_and(|info| {
+                (base as u32..base as u32 + info.arity).contains(&(register as u32))
+            })
+        }
+        _ => false,
+    }
+}
+
+// Whether the value in `register` after instruction `index` is never read.
+fn is_dead(program: &Program, index: usize, register: Register) -> bool {
+    for instruction in &program.instructions()[index + 1..] {
+        if reads(program, *instruction, register) {
+            return false;
+        }
+        match *instruction {
+            Instruction::Call(_, base) if base <= register => return true,
+            Instruction::Return(_) | Instruction::End(_) => return true,
+            instruction if instruction.destination() == Some(register) => return true,
+            _ => {}
+        }
+    }
+    true
+}
+
+pub(crate) fn optimize(program: &Program) -> Program {
+    let code = program.instructions();
+    let mut targets = vec![false; code.len() + 1];
+    targets[program.entry()] = true;
+    for function in program.functions() {
+        targets[function.entry] = true;
+    }
+
+    let mut groups = Vec::with_capacity(code.len());
+    let mut index = 0;
+    while index < code.len() {
+        let instruction = code[index];
+        let next = code.get(index + 1).copied().filter(|_| !targets[index + 1]);
+        match (instruction, next) {
+            (
+                Instruction::Integer(temporary, value),
+                Some(Instruction::Binary(op, dst, left, right)),
+            ) if right == temporary
+                && left != temporary
+                && (dst == temporary || is_dead(program, index + 1, temporary)) =>
+            {
+                groups.push(Vec::new());
+                groups.push(vec![Instruction::BinaryConst(op, dst, left, value)]);
+                index += 2;
+            }
+            (instruction, _)
+                if is_load(instruction)
+                    && instruction
+                        .destination()
+                        .is_some_and(|dst| is_dead(program, index, dst)) =>
+            {
+                groups.push(Vec::new());
+                index += 1;
+            }
+            (instruction, _) => {
+                groups.push(vec![instruction]);
+                index += 1;
+            }
+        }
+    }
+
+    let mut optimized = rebuild(
+        groups,
+        program.line_table(),
+        program.parents(),
+        program.functions(),
+        program.entry(),
+    );
+    optimized.set_header(program.version().to_string(), program.files().to_vec());
+    optimized
+}
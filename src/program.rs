@@ -1,61 +1,323 @@
-use std::{collections::HashMap, error::Error, fs::File};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{instruction::Instruction, token::TokenLocation};
+use crate::{
+    format, instruction::Instruction, token::TokenLocation, Encoding, FileId, LineTable,
+    MigrationHook, Result, SourceMap,
+};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub arity: u32,
+    pub entry: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
     version: String,
+    entry: usize,
     instructions: Box<[Instruction]>,
-    locations: HashMap<usize, TokenLocation>,
+    locations: LineTable,
+    parents: HashMap<usize, usize>,
+    functions: Vec<FunctionInfo>,
+    files: Vec<String>,
+    // Derived from the code, so it is recomputed on load instead of trusted from the file.
+    #[serde(skip)]
+    stack_size: Option<usize>,
+    // Registers of every function's frame followed by the main frame.
+    #[serde(skip)]
+    frame_sizes: Vec<usize>,
+}
+
+// Registers a region uses itself and the calls it makes, as callee region and base register.
+// Broken code still gets a size covering everything it may touch before it fails.
+struct FrameShape {
+    size: usize,
+    calls: Vec<(usize, usize)>,
+    complete: bool,
+}
+
+fn frame(
+    instructions: &[Instruction],
+    functions: &[FunctionInfo],
+    start: usize,
+    size: usize,
+) -> FrameShape {
+    let mut frame = FrameShape {
+        size,
+        calls: Vec::new(),
+        complete: false,
+    };
+    for instruction in instructions.get(start..).unwrap_or_default() {
+        let registers = instruction
+            .destination()
+            .into_iter()
+            .chain(instruction.sources().into_iter().flatten());
+        for register in registers {
+            frame.size = frame.size.max(register as usize + 1);
+        }
+        match *instruction {
+            Instruction::Call(function, base) if (function as usize) < functions.len() => {
+                frame.calls.push((function as usize, base as usize));
+            }
+            Instruction::Call(..) => return frame,
+            Instruction::Return(_) | Instruction::End(_) => {
+                frame.complete = true;
+                return frame;
+            }
+            _ => {}
+        }
+    }
+    frame
+}
+
+// Shapes of every function's frame followed by the main frame.
+fn frames(
+    instructions: &[Instruction],
+    functions: &[FunctionInfo],
+    entry: usize,
+) -> Vec<FrameShape> {
+    functions
+        .iter()
+        .map(|function| (function.entry, function.arity as usize))
+        .chain([(entry, 0)])
+        .map(|(start, size)| frame(instructions, functions, start, size))
+        .collect()
+}
+
+// Words a run needs at most: every region needs its own registers, and every call needs the
+// callee's words on top of its base register. Recursion makes the need unbounded.
+fn stack_size(frames: &[FrameShape]) -> Option<usize> {
+    if !frames.iter().all(|frame| frame.complete) {
+        return None;
+    }
+    let main = frames.len() - 1;
+
+    let mut sizes = vec![None; frames.len()];
+    let mut active = vec![false; frames.len()];
+    let mut pending = vec![(main, 0)];
+    active[main] = true;
+    while let Some(&(region, next)) = pending.last() {
+        match frames[region].calls.get(next) {
+            Some(&(callee, _)) => {
+                pending.last_mut()?.1 += 1;
+                if active[callee] {
+                    return None;
+                }
+                if sizes[callee].is_none() {
+                    active[callee] = true;
+                    pending.push((callee, 0));
+                }
+            }
+            None => {
+                pending.pop();
+                active[region] = false;
+                let mut size = frames[region].size;
+                for &(callee, base) in &frames[region].calls {
+                    size = size.max(base + sizes[callee]?);
+                }
+                sizes[region] = Some(size);
+            }
+        }
+    }
+    sizes[main]
 }
 
 impl Program {
     pub(crate) fn new(
         instructions: Box<[Instruction]>,
-        locations: HashMap<usize, TokenLocation>,
+        locations: LineTable,
+        parents: HashMap<usize, usize>,
+        functions: Vec<FunctionInfo>,
+        entry: usize,
+    ) -> Self {
+        Self::unmeasured(instructions, locations, parents, functions, entry).measured()
+    }
+
+    // Untrusted code is verified before it is measured, overlapping regions make measuring slow.
+    pub(crate) fn unmeasured(
+        instructions: Box<[Instruction]>,
+        locations: LineTable,
+        parents: HashMap<usize, usize>,
+        functions: Vec<FunctionInfo>,
+        entry: usize,
     ) -> Self {
         Self {
             version: env!("CARGO_PKG_VERSION").to_string(),
+            entry,
             instructions,
             locations,
+            parents,
+            functions,
+            files: Vec::new(),
+            stack_size: None,
+            frame_sizes: Vec::new(),
         }
     }
 
+    pub(crate) fn measured(mut self) -> Self {
+        let frames = frames(&self.instructions, &self.functions, self.entry);
+        self.stack_size = stack_size(&frames);
+        self.frame_sizes = frames.iter().map(|frame| frame.size).collect();
+        self
+    }
+
+    // Words of value stack a run needs, None when recursion makes it depend on the input.
+    pub fn stack_size(&self) -> Option<usize> {
+        self.stack_size
+    }
+
+    // Registers the frame of `function` uses, None stands for the main frame.
+    pub fn frame_size(&self, function: Option<usize>) -> usize {
+        let index = function.unwrap_or(self.functions.len());
+        self.frame_sizes.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn line_table(&self) -> &LineTable {
+        &self.locations
+    }
+
+    pub(crate) fn parents(&self) -> &HashMap<usize, usize> {
+        &self.parents
+    }
+
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    pub fn function(&self, index: usize) -> Option<&FunctionInfo> {
+        self.functions.get(index)
+    }
+
+    pub fn functions(&self) -> &[FunctionInfo] {
+        &self.functions
+    }
+
+    pub(crate) fn set_header(&mut self, version: String, files: Vec<String>) {
+        self.version = version;
+        self.files = files;
+    }
+
+    pub(crate) fn set_files(&mut self, sources: &SourceMap) {
+        self.files = sources
+            .files()
+            .map(|(_, file)| file.name().to_string())
+            .collect();
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn file_name(&self, file: FileId) -> Option<&str> {
+        self.files.get(file.index()).map(String::as_str)
+    }
+
     pub fn instruction(&self, index: usize) -> Option<Instruction> {
         self.instructions.get(index).copied()
     }
 
     pub fn location(&self, index: usize) -> Option<TokenLocation> {
-        self.locations.get(&index).copied()
+        self.locations.get(index)
     }
 
-    pub fn save_json(&self, path: &str, pretty: bool) -> Result<(), Box<dyn Error>> {
-        let writer = File::create(path)?;
-        if pretty {
-            serde_json::to_writer_pretty(writer, self)?;
-        } else {
-            serde_json::to_writer(writer, self)?;
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        self.parents
+            .get(&index)
+            .copied()
+            .filter(|parent| *parent > index)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W, encoding: Encoding) -> Result<()> {
+        match encoding {
+            Encoding::Binary => format::write_bin(self, writer),
+            Encoding::Json => format::write_json(self, writer, false),
+            Encoding::PrettyJson => format::write_json(self, writer, true),
         }
-        Ok(())
     }
 
-    pub fn load_json(path: &str) -> Result<Self, Box<dyn Error>> {
-        let reader = File::open(path)?;
-        let result = serde_json::from_reader(reader)?;
-        Ok(result)
+    pub fn read_from<R: Read>(reader: R, encoding: Encoding) -> Result<Self> {
+        Self::read_from_with(reader, encoding, format::migrate)
+    }
+
+    pub fn read_from_with<R: Read>(
+        mut reader: R,
+        encoding: Encoding,
+        hook: MigrationHook,
+    ) -> Result<Self> {
+        match encoding {
+            Encoding::Binary => {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                format::read_bin(bytes, hook)
+            }
+            Encoding::Json | Encoding::PrettyJson => {
+                format::read_json(serde_json::from_reader(reader)?, hook)
+            }
+        }
+    }
+
+    pub fn to_bytes(&self, encoding: Encoding) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes, encoding)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8], encoding: Encoding) -> Result<Self> {
+        Self::read_from(bytes, encoding)
     }
 
-    pub fn save_bin(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        let writer = File::create(path)?;
-        bincode::serialize_into(writer, self)?;
+    fn save(&self, path: &str, encoding: Encoding) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer, encoding)?;
+        writer.flush()?;
         Ok(())
     }
 
-    pub fn load_bin(path: &str) -> Result<Self, Box<dyn Error>> {
-        let reader = File::open(path)?;
-        let result = bincode::deserialize_from(reader)?;
-        Ok(result)
+    fn load(path: &str, encoding: Encoding, hook: MigrationHook) -> Result<Self> {
+        Self::read_from_with(BufReader::new(File::open(path)?), encoding, hook)
+    }
+
+    pub fn save_json(&self, path: &str, pretty: bool) -> Result<()> {
+        let encoding = if pretty {
+            Encoding::PrettyJson
+        } else {
+            Encoding::Json
+        };
+        self.save(path, encoding)
+    }
+
+    pub fn load_json(path: &str) -> Result<Self> {
+        Self::load(path, Encoding::Json, format::migrate)
+    }
+
+    pub fn load_json_with(path: &str, hook: MigrationHook) -> Result<Self> {
+        Self::load(path, Encoding::Json, hook)
+    }
+
+    pub fn save_bin(&self, path: &str) -> Result<()> {
+        self.save(path, Encoding::Binary)
+    }
+
+    pub fn load_bin(path: &str) -> Result<Self> {
+        Self::load(path, Encoding::Binary, format::migrate)
+    }
+
+    pub fn load_bin_with(path: &str, hook: MigrationHook) -> Result<Self> {
+        Self::load(path, Encoding::Binary, hook)
     }
 }
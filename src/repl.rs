@@ -0,0 +1,291 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    compiler::Compiler, lexer::Lexer, token::Token, CompileOptions, Error, FileId, Program,
+    Renderer, Result, State, Value,
+};
+
+const HELP: &str = "\
+Commands:
+    :help           Show this message
+    :type <expr>    Print the type of an expression's value without running it
+    :disasm <code>  Print the bytecode of a line without running it
+    :load <file>    Run a script, keeping its functions and variables
+    :history        Print the inputs of this and earlier sessions
+    :quit           Leave the REPL (Ctrl-D works too)
+
+Input continues on the next line while braces or parentheses are unbalanced,
+a command on its own line drops the unfinished input.";
+
+// History files hold one entry per line, so line breaks inside entries are escaped.
+fn escape(entry: &str) -> String {
+    entry
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            entry.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => entry.push('\n'),
+            Some('r') => entry.push('\r'),
+            Some(c) => entry.push(c),
+            None => entry.push('\\'),
+        }
+    }
+    entry
+}
+
+fn is_complete(source: &str) -> bool {
+    let mut lexer = Lexer::new(source.bytes(), FileId::default());
+    let mut depth = 0isize;
+    loop {
+        match lexer.next() {
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth -= 1,
+            Token::End => return depth <= 0,
+            _ => {}
+        }
+    }
+}
+
+pub struct Repl {
+    state: State,
+    compiler: Compiler,
+    renderer: Renderer,
+    history: Option<PathBuf>,
+    entries: Vec<String>,
+    inputs: usize,
+    // Source file of the last input when it left no code behind, the next input reuses it.
+    scratch: Option<FileId>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_state(State::new())
+    }
+
+    pub fn with_state(state: State) -> Self {
+        Self {
+            compiler: Compiler::with_options(state.compile_options()),
+            state,
+            renderer: Renderer::new().with_color(false),
+            history: None,
+            entries: Vec::new(),
+            inputs: 0,
+            scratch: None,
+        }
+    }
+
+    pub fn with_compile_options(mut self, options: CompileOptions) -> Self {
+        self.state.set_compile_options(options);
+        self.compiler = Compiler::with_options(options);
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: Renderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    // Loads the entries of earlier sessions from `path` and appends new ones to it.
+    pub fn with_history<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        let path = path.into();
+        if let Ok(history) = fs::read_to_string(&path) {
+            self.entries = history.lines().map(unescape).collect();
+        }
+        self.history = Some(path);
+        self
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn source_file(&mut self, name: &str, source: &str) -> FileId {
+        match self.scratch {
+            Some(file) => {
+                self.state.replace_source(file, name, source);
+                file
+            }
+            None => {
+                let file = self.state.add_source(name, source);
+                self.scratch = Some(file);
+                file
+            }
+        }
+    }
+
+    // Compiles an input on top of the session without changing it, see `keep`.
+    fn compile(&mut self, name: &str, source: &str) -> Result<(Compiler, Program)> {
+        let file = self.source_file(name, source);
+        let mut compiler = self.compiler.clone();
+        let program = self.state.compile_input(&mut compiler, file)?;
+        Ok((compiler, program))
+    }
+
+    // Keeps the functions and globals an input declared. Its main code only runs once, so it
+    // is dropped, and its source file only stays when functions still point into it.
+    fn keep(&mut self, mut compiler: Compiler) {
+        compiler.discard_main();
+        if compiler.code_len() > self.compiler.code_len() {
+            self.scratch = None;
+        }
+        self.compiler = compiler;
+    }
+
+    fn next_name(&mut self) -> String {
+        self.inputs += 1;
+        format!("<repl:{}>", self.inputs)
+    }
+
+    pub fn eval_named(&mut self, name: &str, source: &str) -> Result<Value> {
+        let (compiler, program) = self.compile(name, source)?;
+        self.keep(compiler);
+        self.state.execute(&program)
+    }
+
+    pub fn eval(&mut self, source: &str) -> Result<Value> {
+        let name = self.next_name();
+        self.eval_named(&name, source)
+    }
+
+    // The type of the value `source` evaluates to, worked out without running it.
+    pub fn type_of(&mut self, source: &str) -> Result<&'static str> {
+        let name = self.next_name();
+        let (_, program) = self.compile(&name, source)?;
+        program.result_type(self.state.globals())
+    }
+
+    // Lists the code `source` adds to the session: its functions, then its main code.
+    pub fn disassemble(&mut self, source: &str) -> Result<String> {
+        let name = self.next_name();
+        let known = self.compiler.function_count();
+        let (_, program) = self.compile(&name, source)?;
+        let start = program.functions()[known..]
+            .iter()
+            .map(|function| function.entry)
+            .chain([program.entry()])
+            .min()
+            .unwrap_or_default();
+        Ok(program.disassemble_range(start..program.instructions().len()))
+    }
+
+    fn report<W: Write>(&self, out: &mut W, error: &Error) -> io::Result<()> {
+        self.renderer
+            .render(out, &error.diagnostic(), self.state.sources())
+    }
+
+    fn print<W: Write>(&self, out: &mut W, result: Result<Value>) -> io::Result<()> {
+        match result {
+            Ok(Value::Void) => Ok(()),
+            Ok(value) => writeln!(out, "{value}"),
+            Err(error) => self.report(out, &error),
+        }
+    }
+
+    fn save_history(&mut self, entry: &str) {
+        self.entries.push(entry.to_string());
+        if let Some(path) = &self.history {
+            let file = OpenOptions::new().create(true).append(true).open(path);
+            if let Ok(mut file) = file {
+                let _ = writeln!(file, "{}", escape(entry));
+            }
+        }
+    }
+
+    fn command<W: Write>(&mut self, out: &mut W, input: &str) -> io::Result<bool> {
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+        match command {
+            ":quit" | ":q" => return Ok(false),
+            ":help" | ":h" => writeln!(out, "{HELP}")?,
+            ":type" | ":t" => match self.type_of(argument) {
+                Ok(name) => writeln!(out, "{name}")?,
+                Err(error) => self.report(out, &error)?,
+            },
+            ":disasm" | ":d" => match self.disassemble(argument) {
+                Ok(listing) => write!(out, "{listing}")?,
+                Err(error) => self.report(out, &error)?,
+            },
+            ":history" => {
+                for entry in &self.entries {
+                    writeln!(out, "{entry}")?;
+                }
+            }
+            ":load" | ":l" => match fs::read_to_string(argument) {
+                Ok(source) => {
+                    let result = self.eval_named(argument, &source);
+                    self.print(out, result)?
+                }
+                Err(error) => self.report(out, &Error::Io(error))?,
+            },
+            command => writeln!(
+                out,
+                "Unknown command '{command}', type ':help' for a list of commands."
+            )?,
+        }
+        Ok(true)
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, out: &mut W) -> io::Result<()> {
+        let mut buffer = String::new();
+        let mut line = String::new();
+        loop {
+            write!(out, "{}", if buffer.is_empty() { "-> " } else { ".. " })?;
+            out.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(());
+            }
+            // Commands are checked first, so `:quit` still works in unbalanced input.
+            let command = line.trim();
+            if command.starts_with(':') {
+                buffer.clear();
+                self.save_history(command);
+                if !self.command(out, command)? {
+                    return Ok(());
+                }
+                continue;
+            }
+            buffer.push_str(&line);
+            let entry = buffer.trim();
+            if entry.is_empty() {
+                buffer.clear();
+                continue;
+            }
+            if !is_complete(entry) {
+                continue;
+            }
+            let entry = std::mem::take(&mut buffer);
+            let entry = entry.trim();
+            self.save_history(entry);
+            let result = self.eval(entry);
+            self.print(out, result)?;
+        }
+    }
+}
@@ -1,12 +1,17 @@
+use std::{error, fmt};
+
 use crate::token::TokenLocation;
 
+#[derive(Debug)]
 pub struct SourceError {
     pub message: String,
     pub location: TokenLocation,
 }
 
-pub type SourceResult<T> = Result<T, Box<SourceError>>;
-
-pub fn source_error<T>(message: String, location: TokenLocation) -> SourceResult<T> {
-    Err(Box::new(SourceError { message, location }))
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
+
+impl error::Error for SourceError {}
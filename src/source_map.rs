@@ -0,0 +1,71 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileId(u32);
+
+impl FileId {
+    pub(crate) fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+pub struct SourceFile {
+    name: String,
+    source: String,
+}
+
+impl SourceFile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: String, source: String) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile { name, source });
+        id
+    }
+
+    // Reuses a file for another source, for inputs that leave no code behind.
+    pub(crate) fn replace(&mut self, id: FileId, name: String, source: String) {
+        if let Some(file) = self.files.get_mut(id.index()) {
+            *file = SourceFile { name, source };
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<FileId> {
+        let source = fs::read_to_string(path.as_ref())?;
+        Ok(self.add(path.as_ref().display().to_string(), source))
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (FileId(index as u32), file))
+    }
+}
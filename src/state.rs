@@ -1,67 +1,332 @@
-use crate::{vm_error, Instruction, Program, VMResult, Value};
+use std::{mem::size_of, time::Instant};
 
-const STACK_SIZE: usize = 256;
+use crate::{
+    compiler::Compiler,
+    module::Modules,
+    token::TokenLocation,
+    vm_error,
+    word::{Heap, Word},
+    BinaryOperator, CancelToken, CompileOptions, Error, FileId, FileSystemLoader, Instruction,
+    ModuleLoader, Program, Register, Result, RuntimeError, RuntimeErrorKind, SourceMap,
+    StateOptions, VMResult, Value,
+};
+
+// Words reserved up front when the program cannot tell how many it needs.
+const INITIAL_STACK: usize = 256;
+
+// Steps between checks of the cancel token and the deadline.
+const INTERRUPT_INTERVAL: u32 = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub return_address: usize,
+    pub base: usize,
+    pub function: usize,
+}
+
+#[derive(Debug)]
+pub enum StepResult {
+    // The instruction ran and the program has more to run.
+    Running,
+    // `run_until` stopped in front of an instruction the predicate picked.
+    Paused,
+    Finished(Value),
+    Failed(Error),
+}
 
 pub struct State {
-    stack: [Value; STACK_SIZE],
+    stack: Vec<Word>,
+    top: usize,
+    base: usize,
     stack_pointer: usize,
+    heap: Heap,
     program_counter: usize,
+    frames: Vec<Frame>,
+    globals: Vec<Option<Value>>,
+    modules: Modules,
+    options: StateOptions,
+    fuel: Option<u64>,
+    cancel: CancelToken,
+    deadline: Option<Instant>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
+        Self::with_loader(FileSystemLoader)
+    }
+
+    pub fn with_loader<L: ModuleLoader + 'static>(loader: L) -> Self {
         Self {
-            stack: [Value::Void; STACK_SIZE],
+            stack: Vec::new(),
+            top: 0,
+            base: 0,
             stack_pointer: 0,
+            heap: Heap::new(),
             program_counter: 0,
+            frames: Vec::new(),
+            globals: Vec::new(),
+            modules: Modules::new(Box::new(loader)),
+            options: StateOptions::new(),
+            fuel: None,
+            cancel: CancelToken::new(),
+            deadline: None,
         }
     }
 
-    fn push(&mut self, value: Value) -> VMResult {
-        if self.stack_pointer < STACK_SIZE {
-            self.stack[self.stack_pointer] = value;
-            self.stack_pointer += 1;
-            Ok(())
-        } else {
-            vm_error(format!("Stack overflow"))
+    pub fn with_options(options: StateOptions) -> Self {
+        let mut state = Self::new();
+        state.set_options(options);
+        state
+    }
+
+    pub fn options(&self) -> StateOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: StateOptions) {
+        self.options = options;
+    }
+
+    pub fn compile(&mut self, name: &str, source: &str) -> Result<Program> {
+        self.modules.compile_source(name, source)
+    }
+
+    pub fn compile_path(&mut self, path: &str) -> Result<Program> {
+        self.modules.compile_path(path)
+    }
+
+    pub(crate) fn add_source(&mut self, name: &str, source: &str) -> FileId {
+        self.modules.add_source(name, source)
+    }
+
+    pub(crate) fn replace_source(&mut self, file: FileId, name: &str, source: &str) {
+        self.modules.replace_source(file, name, source);
+    }
+
+    pub(crate) fn compile_with(
+        &mut self,
+        compiler: &mut Compiler,
+        file: FileId,
+    ) -> Result<Program> {
+        self.modules.compile_with(compiler, file)
+    }
+
+    pub(crate) fn compile_input(
+        &mut self,
+        compiler: &mut Compiler,
+        file: FileId,
+    ) -> Result<Program> {
+        self.modules.compile_input(compiler, file)
+    }
+
+    pub fn compile_options(&self) -> CompileOptions {
+        self.modules.options()
+    }
+
+    pub fn set_compile_options(&mut self, options: CompileOptions) {
+        self.modules.set_options(options);
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        self.modules.sources()
+    }
+
+    fn encode(&mut self, value: Value) -> VMResult<Word> {
+        let budget = self.options.max_memory().saturating_sub(self.memory_used());
+        match self.heap.encode(value, &mut self.stack[..self.top], budget) {
+            Some(word) => Ok(word),
+            None => vm_error(RuntimeErrorKind::OutOfMemory),
         }
     }
 
-    fn pop(&mut self) -> VMResult<Value> {
-        if self.stack_pointer == 0 {
-            vm_error(format!("Stack underflow"))
-        } else {
-            self.stack_pointer -= 1;
-            Ok(self.stack[self.stack_pointer])
+    // Bytes held by the value stack, call frames, globals and boxed values.
+    pub fn memory_used(&self) -> usize {
+        self.stack.capacity() * size_of::<Word>()
+            + self.frames.capacity() * size_of::<Frame>()
+            + self.globals.capacity() * size_of::<Option<Value>>()
+            + self.heap.bytes()
+    }
+
+    // Checked before anything grows, so a run over the limit fails instead of allocating.
+    fn allocate(&self, bytes: usize) -> VMResult {
+        match self.memory_used().checked_add(bytes) {
+            Some(total) if total <= self.options.max_memory() => Ok(()),
+            _ => vm_error(RuntimeErrorKind::OutOfMemory),
         }
     }
 
-    fn integer(&mut self, value: i64) -> VMResult<bool> {
-        self.push(Value::Integer(value))?;
+    fn decode(&self, word: Word) -> Value {
+        self.heap.decode(word)
+    }
+
+    fn register(&self, register: Register) -> VMResult<Word> {
+        let index = self.base + register as usize;
+        match self.stack.get(index) {
+            Some(word) => Ok(*word),
+            None if index < self.options.max_stack() => Ok(Word::VOID),
+            None => vm_error(RuntimeErrorKind::StackOverflow),
+        }
+    }
+
+    // Reserves at least `size` words, doubling so a deep run only reallocates a few times.
+    #[cold]
+    fn reserve(&mut self, size: usize) -> VMResult {
+        let max = self.options.max_stack();
+        if size > max {
+            return vm_error(RuntimeErrorKind::StackOverflow);
+        }
+        if size > self.stack.len() {
+            let mut grown = size.max(self.stack.len() * 2).min(max);
+            let bytes =
+                |words: usize| words.saturating_sub(self.stack.capacity()) * size_of::<Word>();
+            if self.allocate(bytes(grown)).is_err() {
+                self.allocate(bytes(size))?;
+                grown = size;
+            }
+            self.stack.reserve_exact(grown - self.stack.len());
+            self.stack.resize(grown, Word::VOID);
+        }
+        Ok(())
+    }
+
+    fn set_register(&mut self, register: Register, word: Word) -> VMResult {
+        let index = self.base + register as usize;
+        match self.stack.get_mut(index) {
+            Some(slot) => *slot = word,
+            None => {
+                self.reserve(index + 1)?;
+                self.stack[index] = word;
+            }
+        }
+        self.top = self.top.max(index + 1);
+        Ok(())
+    }
+
+    fn load(&mut self, dst: Register, value: Value) -> VMResult<bool> {
+        let word = self.encode(value)?;
+        self.set_register(dst, word)?;
+        self.program_counter += 1;
+        Ok(true)
+    }
+
+    fn copy(&mut self, dst: Register, src: Register) -> VMResult<bool> {
+        let word = self.register(src)?;
+        self.set_register(dst, word)?;
         self.program_counter += 1;
         Ok(true)
     }
 
-    fn float(&mut self, value: f64) -> VMResult<bool> {
-        self.push(Value::Float(value))?;
+    fn global(&mut self, dst: Register, slot: u32) -> VMResult<bool> {
+        match self.globals.get(slot as usize) {
+            Some(Some(value)) => self.load(dst, *value),
+            _ => vm_error(RuntimeErrorKind::UndefinedGlobal(slot)),
+        }
+    }
+
+    fn set_global(&mut self, slot: u32, src: Register) -> VMResult<bool> {
+        let value = self.decode(self.register(src)?);
+        let slot = slot as usize;
+        if slot >= self.globals.len() {
+            let capacity = self.globals.capacity();
+            if slot >= capacity {
+                let grown = (slot + 1).max(capacity * 2);
+                self.allocate((grown - capacity) * size_of::<Option<Value>>())?;
+                self.globals.reserve_exact(grown - self.globals.len());
+            }
+            self.globals.resize(slot + 1, None);
+        }
+        self.globals[slot] = Some(value);
         self.program_counter += 1;
         Ok(true)
     }
 
-    fn boolean(&mut self, value: bool) -> VMResult<bool> {
-        self.push(Value::Boolean(value))?;
+    fn call(&mut self, program: &Program, function: u32, base: Register) -> VMResult<bool> {
+        let Some(info) = program.function(function as usize) else {
+            return vm_error(RuntimeErrorKind::InvalidFunction(function));
+        };
+        if self.frames.len() >= self.options.max_stack() {
+            return vm_error(RuntimeErrorKind::StackOverflow);
+        }
+        let base = self.base + base as usize;
+        // The result goes to the base register, so it needs a slot even without arguments.
+        let needed = base + (info.arity as usize).max(1);
+        if needed > self.stack.len() {
+            self.reserve(needed)?;
+        }
+        if self.frames.len() == self.frames.capacity() {
+            self.allocate(self.frames.capacity().max(4) * size_of::<Frame>())?;
+        }
+        self.frames.push(Frame {
+            return_address: self.program_counter + 1,
+            base,
+            function: function as usize,
+        });
+        self.base = base;
+        self.stack_pointer = base + program.frame_size(Some(function as usize));
+        self.program_counter = info.entry;
+        Ok(true)
+    }
+
+    fn ret(&mut self, program: &Program, src: Register) -> VMResult<bool> {
+        let result = self.register(src)?;
+        let Some(frame) = self.frames.pop() else {
+            return vm_error(RuntimeErrorKind::StackUnderflow);
+        };
+        self.stack[frame.base] = result;
+        self.top = self.top.max(frame.base + 1);
+        let caller = self.frames.last();
+        self.base = caller.map_or(0, |frame| frame.base);
+        self.stack_pointer = self.base + program.frame_size(caller.map(|frame| frame.function));
+        self.program_counter = frame.return_address;
+        Ok(true)
+    }
+
+    fn apply(&mut self, op: BinaryOperator, dst: Register, left: Word, right: Word) -> VMResult {
+        let result = match Word::binary(op, left, right) {
+            Some(result) => result,
+            None => {
+                let value = self.decode(left).binary(op, self.decode(right))?;
+                self.encode(value)?
+            }
+        };
+        self.set_register(dst, result)
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOperator,
+        dst: Register,
+        left: Register,
+        right: Register,
+    ) -> VMResult<bool> {
+        let (left, right) = (self.register(left)?, self.register(right)?);
+        self.apply(op, dst, left, right)?;
         self.program_counter += 1;
         Ok(true)
     }
 
-    fn binary<F>(&mut self, f: F) -> VMResult<bool>
-    where
-        F: Fn(&mut Self, Value, Value) -> VMResult<Value>,
-    {
-        let right = self.pop()?;
-        let left = self.pop()?;
-        let result = f(self, left, right)?;
-        self.push(result)?;
+    fn binary_const(
+        &mut self,
+        op: BinaryOperator,
+        dst: Register,
+        left: Register,
+        right: i64,
+    ) -> VMResult<bool> {
+        let left = self.register(left)?;
+        match Word::small(right) {
+            Some(right) => self.apply(op, dst, left, right)?,
+            None => {
+                let value = self.decode(left).binary(op, Value::Integer(right))?;
+                let word = self.encode(value)?;
+                self.set_register(dst, word)?;
+            }
+        }
         self.program_counter += 1;
         Ok(true)
     }
@@ -73,37 +338,264 @@ impl State {
     fn fetch(&mut self, program: &Program) -> VMResult<Instruction> {
         match program.instruction(self.program_counter) {
             Some(instruction) => Ok(instruction),
-            None => vm_error(format!("Program counter out of bounds")),
+            None => vm_error(RuntimeErrorKind::ProgramCounterOutOfBounds),
         }
     }
 
-    fn step(&mut self, program: &Program) -> VMResult<bool> {
+    fn dispatch(&mut self, program: &Program) -> VMResult<bool> {
         let instruction = self.fetch(program)?;
         match instruction {
-            Instruction::Integer(value) => self.integer(value),
-            Instruction::Float(value) => self.float(value),
-            Instruction::Boolean(value) => self.boolean(value),
-            Instruction::Addict => self.binary(Self::addict),
-            Instruction::Subtract => self.binary(Self::subtract),
-            Instruction::Multiply => self.binary(Self::multiply),
-            Instruction::Divide => self.binary(Self::divide),
-            Instruction::Modulo => self.binary(Self::modulo),
-            Instruction::Equals => self.binary(Self::equals),
-            Instruction::NotEquals => self.binary(Self::not_equals),
-            Instruction::Less => self.binary(Self::less),
-            Instruction::Greater => self.binary(Self::greater),
-            Instruction::LessEquals => self.binary(Self::less_equals),
-            Instruction::GreaterEquals => self.binary(Self::greater_equals),
-            Instruction::End => self.end(),
+            Instruction::Integer(dst, value) => self.load(dst, Value::Integer(value)),
+            Instruction::Float(dst, value) => self.load(dst, Value::Float(value)),
+            Instruction::Boolean(dst, value) => self.load(dst, Value::Boolean(value)),
+            Instruction::Void(dst) => self.load(dst, Value::Void),
+            Instruction::Move(dst, src) => self.copy(dst, src),
+            Instruction::Binary(op, dst, left, right) => self.binary(op, dst, left, right),
+            Instruction::BinaryConst(op, dst, left, right) => {
+                self.binary_const(op, dst, left, right)
+            }
+            Instruction::Global(dst, slot) => self.global(dst, slot),
+            Instruction::SetGlobal(slot, src) => self.set_global(slot, src),
+            Instruction::Call(function, base) => self.call(program, function, base),
+            Instruction::Return(src) => self.ret(program, src),
+            Instruction::End(_) => self.end(),
         }
     }
 
+    fn interrupted(&self) -> VMResult {
+        if self.cancel.is_cancelled() {
+            return vm_error(RuntimeErrorKind::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return vm_error(RuntimeErrorKind::TimedOut);
+        }
+        Ok(())
+    }
+
+    // Fuel is taken before the instruction runs, so running dry leaves it to execute on resume.
+    // The interruption checks are spread out with `countdown`, which starts at 1 to check first.
+    #[inline(always)]
+    fn advance(&mut self, program: &Program, countdown: &mut u32) -> VMResult<bool> {
+        *countdown -= 1;
+        if *countdown == 0 {
+            self.interrupted()?;
+            *countdown = INTERRUPT_INTERVAL;
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return vm_error(RuntimeErrorKind::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
+        self.dispatch(program)
+    }
+
+    fn result(&mut self, program: &Program) -> VMResult<Value> {
+        match self.fetch(program)? {
+            Instruction::End(src) => Ok(self.decode(self.register(src)?)),
+            _ => vm_error(RuntimeErrorKind::ProgramCounterOutOfBounds),
+        }
+    }
+
+    fn finish(&mut self, program: &Program) -> VMResult<Value> {
+        let mut countdown = 1;
+        while self.advance(program, &mut countdown)? {}
+        self.result(program)
+    }
+
+    fn prepare(&mut self, program: &Program) -> VMResult {
+        self.program_counter = program.entry();
+        self.base = 0;
+        self.stack_pointer = program.frame_size(None);
+        self.top = 0;
+        self.frames.clear();
+        self.heap.clear();
+        self.fuel = self.options.fuel();
+        self.deadline = self
+            .options
+            .timeout()
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        // When the whole stack does not fit the memory limit, growing on demand lets the error
+        // point at the instruction that needs the memory.
+        let size = program.stack_size().unwrap_or(INITIAL_STACK);
+        let size = size.min(self.options.max_stack());
+        if self.reserve(size).is_err() {
+            self.reserve(size.min(INITIAL_STACK))?;
+        }
+        Ok(())
+    }
+
     pub fn run(&mut self, program: &Program) -> VMResult<Value> {
-        while self.step(program)? {}
-        self.pop()
+        self.prepare(program)?;
+        self.finish(program)
+    }
+
+    fn error(&self, program: &Program, kind: RuntimeErrorKind) -> Error {
+        Error::Runtime(Box::new(RuntimeError {
+            kind,
+            trace: self.trace(program),
+        }))
+    }
+
+    fn located<T>(&self, program: &Program, result: VMResult<T>) -> Result<T> {
+        result.map_err(|kind| self.error(program, *kind))
+    }
+
+    pub fn execute(&mut self, program: &Program) -> Result<Value> {
+        let result = self.run(program);
+        self.located(program, result)
+    }
+
+    // Runs `program` with `arguments` in its first registers, for code compiled with a frame.
+    pub(crate) fn execute_in_frame(
+        &mut self,
+        program: &Program,
+        arguments: &[Value],
+    ) -> Result<Value> {
+        let result = self.prepare(program).and_then(|()| {
+            for (register, value) in arguments.iter().enumerate() {
+                let word = self.encode(*value)?;
+                self.set_register(register as Register, word)?;
+            }
+            self.finish(program)
+        });
+        self.located(program, result)
+    }
+
+    // Continues a run that stopped, after running out of fuel or being paused while stepping.
+    pub fn resume(&mut self, program: &Program) -> Result<Value> {
+        let result = self.finish(program);
+        self.located(program, result)
+    }
+
+    // Sets up a run of `program` without executing anything, for stepping through it.
+    pub fn start(&mut self, program: &Program) -> Result<()> {
+        let result = self.prepare(program);
+        self.located(program, result)
+    }
+
+    fn stepped(&mut self, program: &Program, result: VMResult<bool>) -> StepResult {
+        let result = match result {
+            Ok(true) => return StepResult::Running,
+            Ok(false) => self.result(program),
+            Err(kind) => Err(kind),
+        };
+        match result {
+            Ok(value) => StepResult::Finished(value),
+            Err(kind) => StepResult::Failed(self.error(program, *kind)),
+        }
+    }
+
+    // Executes one instruction. Stepping past the end keeps returning the result.
+    pub fn step(&mut self, program: &Program) -> StepResult {
+        let result = self.advance(program, &mut 1);
+        self.stepped(program, result)
+    }
+
+    // Runs at least one instruction, then stops in front of the first one `predicate` accepts.
+    pub fn run_until<P: FnMut(&State) -> bool>(
+        &mut self,
+        program: &Program,
+        mut predicate: P,
+    ) -> StepResult {
+        let mut countdown = 1;
+        loop {
+            match self.advance(program, &mut countdown) {
+                Ok(true) if predicate(self) => return StepResult::Paused,
+                Ok(true) => {}
+                result => return self.stepped(program, result),
+            }
+        }
+    }
+
+    // Values of every frame up to the end of the current one, registers this run has not
+    // written yet read as void.
+    pub fn stack(&self) -> Vec<Value> {
+        (0..self.stack_pointer)
+            .map(|index| match self.stack.get(index) {
+                Some(word) if index < self.top => self.decode(*word),
+                _ => Value::Void,
+            })
+            .collect()
+    }
+
+    pub fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    // Index of the current frame's first register in `stack`.
+    pub fn frame_base(&self) -> usize {
+        self.base
+    }
+
+    pub fn location(&self, program: &Program) -> Option<TokenLocation> {
+        program.location(self.program_counter)
+    }
+
+    // A handle that cancels this state's runs from any thread.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    // Replaces the deadline of the current run, for example to give a resumed run more time.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Adds to the remaining fuel, a state without a fuel limit stays unlimited.
+    pub fn refuel(&mut self, fuel: u64) {
+        if let Some(remaining) = &mut self.fuel {
+            *remaining = remaining.saturating_add(fuel);
+        }
+    }
+
+    pub(crate) fn globals(&self) -> &[Option<Value>] {
+        &self.globals
+    }
+
+    pub(crate) fn set_globals(&mut self, globals: Vec<Option<Value>>) {
+        self.globals = globals;
     }
 
     pub fn program_counter(&self) -> usize {
         self.program_counter
     }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    fn trace_from(program: &Program, index: Option<usize>, trace: &mut Vec<TokenLocation>) {
+        let mut index = index;
+        while let Some(current) = index {
+            if let Some(location) = program.location(current) {
+                trace.push(location);
+            }
+            index = program.parent(current);
+        }
+    }
+
+    pub fn trace(&self, program: &Program) -> Vec<TokenLocation> {
+        let mut trace = Vec::new();
+        Self::trace_from(program, Some(self.program_counter), &mut trace);
+        for frame in self.frames.iter().rev() {
+            Self::trace_from(program, frame.return_address.checked_sub(1), &mut trace);
+        }
+        trace
+    }
 }
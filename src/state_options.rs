@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateOptions {
+    max_stack: usize,
+    max_memory: usize,
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl Default for StateOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateOptions {
+    // One million words, 8 MiB of value stack.
+    pub const DEFAULT_MAX_STACK: usize = 1 << 20;
+    pub const DEFAULT_MAX_MEMORY: usize = 256 << 20;
+
+    pub fn new() -> Self {
+        Self {
+            max_stack: Self::DEFAULT_MAX_STACK,
+            max_memory: Self::DEFAULT_MAX_MEMORY,
+            fuel: None,
+            timeout: None,
+        }
+    }
+
+    // The limit is counted in stack words, which is also the most frames a run may nest.
+    pub fn with_max_stack(mut self, words: usize) -> Self {
+        self.max_stack = words.max(1);
+        self
+    }
+
+    pub fn max_stack(&self) -> usize {
+        self.max_stack
+    }
+
+    // Bytes the value stack, call frames, globals and boxed values may hold together.
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    pub fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    // Every run starts with this many instructions to execute, without it runs are unlimited.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Every run must finish within this time after it starts.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
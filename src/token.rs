@@ -2,7 +2,10 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use crate::identifiers::{IdentifierId, Identifiers};
+use crate::{
+    identifiers::{IdentifierId, Identifiers},
+    FileId,
+};
 
 #[derive(PartialEq)]
 pub enum Token {
@@ -21,16 +24,31 @@ pub enum Token {
     GreaterEquals,     // '>='
     Exclamation,       // '!'
     ExclamationEquals, // '!='
+    Semicolon,         // ';'
+    Comma,             // ','
+    Dot,               // '.'
+    LeftParen,         // '('
+    RightParen,        // ')'
+    LeftBrace,         // '{'
+    RightBrace,        // '}'
     True,              // 'true'
     False,             // 'false'
+    Fn,                // 'fn'
+    Import,            // 'import'
+    As,                // 'as'
+    Let,               // 'let'
     Identifier(IdentifierId),
+    String(IdentifierId),
     Unknown(u8),
+    UnterminatedString,
     ToBigInteger,
     End,
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TokenLocation {
+    pub file: FileId,
+    pub offset: u32,
     pub line: u32,
     pub column: u32,
     pub length: u32,
@@ -72,10 +90,27 @@ impl<'a, 'b> fmt::Display for TokenWriter<'a, 'b> {
             Token::GreaterEquals => write!(f, ">="),
             Token::Exclamation => write!(f, "!"),
             Token::ExclamationEquals => write!(f, "!="),
+            Token::Semicolon => write!(f, ";"),
+            Token::Comma => write!(f, ","),
+            Token::Dot => write!(f, "."),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::LeftBrace => write!(f, "{{"),
+            Token::RightBrace => write!(f, "}}"),
             Token::True => write!(f, "true"),
             Token::False => write!(f, "false"),
+            Token::Fn => write!(f, "fn"),
+            Token::Import => write!(f, "import"),
+            Token::As => write!(f, "as"),
+            Token::Let => write!(f, "let"),
             Token::Identifier(id) => write_u8_slice(f, self.identifiers.get(id)),
+            Token::String(id) => {
+                write!(f, "\"")?;
+                write_u8_slice(f, self.identifiers.get(id))?;
+                write!(f, "\"")
+            }
             Token::Unknown(c) => write!(f, "{}", *c as char),
+            Token::UnterminatedString => write!(f, "unterminated string"),
             Token::ToBigInteger => write!(f, "to big integer"),
             Token::End => write!(f, ""),
         }
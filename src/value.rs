@@ -1,8 +1,8 @@
 use std::fmt;
 
-use crate::{vm_error, State, VMResult};
+use crate::{vm_error, BinaryOperator, RuntimeErrorKind, State, VMResult};
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
     Void,
     Boolean(bool),
@@ -10,6 +10,17 @@ pub enum Value {
     Float(f64),
 }
 
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Void => "void",
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -21,42 +32,54 @@ impl fmt::Display for Value {
     }
 }
 
-impl State {
-    pub fn addict(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+impl Value {
+    pub fn addict(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Integer(l.wrapping_add(r))),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(l as f64 + r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l + r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l + r)),
-            (l, r) => vm_error(format!("Unable to addict '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Addict,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn subtract(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn subtract(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Integer(l.wrapping_sub(r))),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(l as f64 - r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l - r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l - r)),
-            (l, r) => vm_error(format!("Unable to subtract '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Subtract,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn multiply(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn multiply(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Integer(l.wrapping_mul(r))),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(l as f64 * r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l * r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l * r)),
-            (l, r) => vm_error(format!("Unable to multiply '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Multiply,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn divide(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn divide(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => {
                 if r == 0 {
-                    vm_error(format!("Dividing by zero."))
+                    vm_error(RuntimeErrorKind::DivisionByZero)
                 } else {
                     Ok(Value::Integer(l.wrapping_div(r)))
                 }
@@ -64,15 +87,19 @@ impl State {
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(l as f64 / r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l / r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l / r)),
-            (l, r) => vm_error(format!("Unable to divide '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Divide,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn modulo(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn modulo(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => {
                 if r == 0 {
-                    vm_error(format!("Dividing by zero."))
+                    vm_error(RuntimeErrorKind::DivisionByZero)
                 } else {
                     Ok(Value::Integer(l.wrapping_rem(r)))
                 }
@@ -80,67 +107,159 @@ impl State {
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(l as f64 % r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l % r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l % r)),
-            (l, r) => vm_error(format!("Unable to modulo '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Modulo,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn less(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn less(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l < r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) < r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l < r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l < r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Less,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn greater(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn greater(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l > r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) > r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l > r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l > r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Greater,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn equals(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l == r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) == r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l == r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l == r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::Equals,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn not_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn not_equals(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l != r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) != r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l != r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l != r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::NotEquals,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn less_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn less_equals(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l <= r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) <= r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l <= r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l <= r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::LessEquals,
+                left,
+                right,
+            }),
         }
     }
 
-    pub fn greater_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
-        match (l, r) {
+    pub fn greater_equals(self, r: Value) -> VMResult<Value> {
+        match (self, r) {
             (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l >= r)),
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Boolean((l as f64) >= r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Boolean(l >= r as f64)),
             (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l >= r)),
-            (l, r) => vm_error(format!("Unable to compare '{l}' and '{r}'")),
+            (left, right) => vm_error(RuntimeErrorKind::TypeMismatch {
+                op: BinaryOperator::GreaterEquals,
+                left,
+                right,
+            }),
+        }
+    }
+}
+
+impl Value {
+    pub(crate) fn binary(self, op: BinaryOperator, r: Value) -> VMResult<Value> {
+        match op {
+            BinaryOperator::Addict => self.addict(r),
+            BinaryOperator::Subtract => self.subtract(r),
+            BinaryOperator::Multiply => self.multiply(r),
+            BinaryOperator::Divide => self.divide(r),
+            BinaryOperator::Modulo => self.modulo(r),
+            BinaryOperator::Equals => self.equals(r),
+            BinaryOperator::NotEquals => self.not_equals(r),
+            BinaryOperator::Less => self.less(r),
+            BinaryOperator::Greater => self.greater(r),
+            BinaryOperator::LessEquals => self.less_equals(r),
+            BinaryOperator::GreaterEquals => self.greater_equals(r),
         }
     }
 }
+
+impl State {
+    pub fn addict(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.addict(r)
+    }
+
+    pub fn subtract(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.subtract(r)
+    }
+
+    pub fn multiply(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.multiply(r)
+    }
+
+    pub fn divide(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.divide(r)
+    }
+
+    pub fn modulo(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.modulo(r)
+    }
+
+    pub fn less(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.less(r)
+    }
+
+    pub fn greater(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.greater(r)
+    }
+
+    pub fn equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.equals(r)
+    }
+
+    pub fn not_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.not_equals(r)
+    }
+
+    pub fn less_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.less_equals(r)
+    }
+
+    pub fn greater_equals(&mut self, l: Value, r: Value) -> VMResult<Value> {
+        l.greater_equals(r)
+    }
+}
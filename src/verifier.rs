@@ -0,0 +1,205 @@
+use std::{error, fmt};
+
+use crate::{Error, Instruction, Program, Result, StateOptions};
+
+#[derive(Debug)]
+pub struct VerifyError {
+    pub message: String,
+    pub index: Option<usize>,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{} (instruction {index})", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for VerifyError {}
+
+fn verify_error<T>(message: String, index: Option<usize>) -> Result<T> {
+    Err(Error::Verify(Box::new(VerifyError { message, index })))
+}
+
+enum Region {
+    Main,
+    Function(usize),
+}
+
+impl Program {
+    fn verify_region(&self, region: Region, start: usize, max_stack: usize) -> Result<()> {
+        // Registers written on the way through the region, parameters arrive already written.
+        let mut written = match region {
+            Region::Main => Vec::new(),
+            Region::Function(function) => vec![true; self.functions()[function].arity as usize],
+        };
+        let mut index = start;
+        loop {
+            let Some(instruction) = self.instruction(index) else {
+                return verify_error(
+                    format!("Code starting at {start} runs past the end of the program"),
+                    Some(index),
+                );
+            };
+            let mut reads: Vec<usize> = instruction
+                .sources()
+                .into_iter()
+                .flatten()
+                .map(usize::from)
+                .collect();
+            if let Instruction::Call(function, base) = instruction {
+                let Some(info) = self.function(function as usize) else {
+                    return verify_error(
+                        format!("Call to unknown function {function}"),
+                        Some(index),
+                    );
+                };
+                // Like the VM, count the result slot of calls without arguments.
+                if base as usize + (info.arity as usize).max(1) > max_stack {
+                    return verify_error(
+                        format!("Call at r{base} exceeds the stack size of {max_stack}"),
+                        Some(index),
+                    );
+                }
+                reads.extend(base as usize..base as usize + info.arity as usize);
+            }
+            for register in reads {
+                if !written.get(register).copied().unwrap_or(false) {
+                    return verify_error(
+                        format!("'{instruction}' reads r{register} before it is written"),
+                        Some(index),
+                    );
+                }
+            }
+            if let Some(register) = instruction.destination() {
+                let register = register as usize;
+                if register >= max_stack {
+                    return verify_error(
+                        format!("Register r{register} exceeds the stack size of {max_stack}"),
+                        Some(index),
+                    );
+                }
+                if written.len() <= register {
+                    written.resize(register + 1, false);
+                }
+                written[register] = true;
+                // The callee's frame overwrites everything above its result.
+                if let Instruction::Call(..) = instruction {
+                    written.truncate(register + 1);
+                }
+            }
+            match (instruction, &region) {
+                (Instruction::End(_), Region::Main)
+                | (Instruction::Return(_), Region::Function(_)) => return Ok(()),
+                (Instruction::Return(_), Region::Main) => {
+                    return verify_error("Return outside of a function".to_string(), Some(index))
+                }
+                (Instruction::End(_), Region::Function(function)) => {
+                    return verify_error(
+                        format!(
+                            "Function '{}' reaches End instead of Return",
+                            self.functions()[*function].name
+                        ),
+                        Some(index),
+                    )
+                }
+                _ => index += 1,
+            }
+        }
+    }
+
+    fn verify_locations(&self) -> Result<()> {
+        let length = self.instructions().len();
+        if !self.line_table().is_sorted() {
+            return verify_error("Line table is not sorted".to_string(), None);
+        }
+        for (index, location) in self.line_table().entries() {
+            if index >= length {
+                return verify_error(
+                    "Location is attached to a missing instruction".to_string(),
+                    Some(index),
+                );
+            }
+            if !self.files().is_empty() && location.file.index() >= self.files().len() {
+                return verify_error(
+                    format!("Location refers to unknown file {}", location.file.index()),
+                    Some(index),
+                );
+            }
+        }
+        for (child, parent) in self.parents() {
+            if *child >= length || *parent >= length {
+                return verify_error(
+                    format!("Parent link {child} -> {parent} points outside of the program"),
+                    Some(*child),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        self.verify_with_stack(StateOptions::DEFAULT_MAX_STACK)
+    }
+
+    // Rejects entries that are shared or fall inside another region. Every function and the
+    // main code then own their stretch of code, so checking all regions visits every
+    // instruction once.
+    fn verify_entries(&self) -> Result<()> {
+        let mut regions: Vec<(usize, &str)> = self
+            .functions()
+            .iter()
+            .map(|function| (function.entry, function.name.as_str()))
+            .chain([(self.entry(), "main")])
+            .collect();
+        regions.sort_by_key(|(entry, _)| *entry);
+        for pair in regions.windows(2) {
+            let ((start, name), (next, other)) = (pair[0], pair[1]);
+            if start == next {
+                return verify_error(
+                    format!("'{name}' and '{other}' both start at {start}"),
+                    Some(start),
+                );
+            }
+            let ends = self.instructions()[start..next].iter().any(|instruction| {
+                matches!(instruction, Instruction::Return(_) | Instruction::End(_))
+            });
+            if !ends {
+                return verify_error(
+                    format!("'{other}' starts at {next} inside the code of '{name}'"),
+                    Some(next),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Also rejects frames that could never fit into a stack of `max_stack` words.
+    pub fn verify_with_stack(&self, max_stack: usize) -> Result<()> {
+        if self.entry() >= self.instructions().len() {
+            return verify_error(
+                format!("Entry point {} is outside of the program", self.entry()),
+                None,
+            );
+        }
+        for function in self.functions() {
+            if function.entry >= self.instructions().len() {
+                return verify_error(
+                    format!(
+                        "Function '{}' starts at {} outside of the program",
+                        function.name, function.entry
+                    ),
+                    None,
+                );
+            }
+        }
+        self.verify_entries()?;
+        for (index, function) in self.functions().iter().enumerate() {
+            self.verify_region(Region::Function(index), function.entry, max_stack)?;
+        }
+        self.verify_region(Region::Main, self.entry(), max_stack)?;
+        self.verify_locations()
+    }
+}
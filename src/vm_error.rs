@@ -1,6 +1,151 @@
-pub type VMError = Box<Box<str>>;
-pub type VMResult<T = ()> = Result<T, VMError>;
+use std::{error, fmt};
 
-pub(crate) fn vm_error<T>(message: String) -> VMResult<T> {
-    Err(VMError::new(message.into_boxed_str()))
+use serde::{Deserialize, Serialize};
+
+use crate::{token::TokenLocation, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryOperator {
+    Addict,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equals,
+    NotEquals,
+    Less,
+    Greater,
+    LessEquals,
+    GreaterEquals,
+}
+
+impl BinaryOperator {
+    pub const ALL: [BinaryOperator; 11] = [
+        Self::Addict,
+        Self::Subtract,
+        Self::Multiply,
+        Self::Divide,
+        Self::Modulo,
+        Self::Equals,
+        Self::NotEquals,
+        Self::Less,
+        Self::Greater,
+        Self::LessEquals,
+        Self::GreaterEquals,
+    ];
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Addict => "Addict",
+            Self::Subtract => "Subtract",
+            Self::Multiply => "Multiply",
+            Self::Divide => "Divide",
+            Self::Modulo => "Modulo",
+            Self::Equals => "Equals",
+            Self::NotEquals => "NotEquals",
+            Self::Less => "Less",
+            Self::Greater => "Greater",
+            Self::LessEquals => "LessEquals",
+            Self::GreaterEquals => "GreaterEquals",
+        }
+    }
+
+    pub fn const_mnemonic(self) -> &'static str {
+        match self {
+            Self::Addict => "AddictConst",
+            Self::Subtract => "SubtractConst",
+            Self::Multiply => "MultiplyConst",
+            Self::Divide => "DivideConst",
+            Self::Modulo => "ModuloConst",
+            Self::Equals => "EqualsConst",
+            Self::NotEquals => "NotEqualsConst",
+            Self::Less => "LessConst",
+            Self::Greater => "GreaterConst",
+            Self::LessEquals => "LessEqualsConst",
+            Self::GreaterEquals => "GreaterEqualsConst",
+        }
+    }
+
+    pub fn verb(self) -> &'static str {
+        match self {
+            Self::Addict => "addict",
+            Self::Subtract => "subtract",
+            Self::Multiply => "multiply",
+            Self::Divide => "divide",
+            Self::Modulo => "modulo",
+            Self::Equals
+            | Self::NotEquals
+            | Self::Less
+            | Self::Greater
+            | Self::LessEquals
+            | Self::GreaterEquals => "compare",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimeErrorKind {
+    DivisionByZero,
+    TypeMismatch {
+        op: BinaryOperator,
+        left: Value,
+        right: Value,
+    },
+    StackOverflow,
+    StackUnderflow,
+    ProgramCounterOutOfBounds,
+    InvalidFunction(u32),
+    UndefinedGlobal(u32),
+    OutOfFuel,
+    Cancelled,
+    TimedOut,
+    OutOfMemory,
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "Dividing by zero."),
+            Self::TypeMismatch { op, left, right } => {
+                write!(f, "Unable to {} '{left}' and '{right}'", op.verb())
+            }
+            Self::StackOverflow => write!(f, "Stack overflow"),
+            Self::StackUnderflow => write!(f, "Stack underflow"),
+            Self::ProgramCounterOutOfBounds => write!(f, "Program counter out of bounds"),
+            Self::InvalidFunction(index) => write!(f, "Invalid function index {index}"),
+            Self::UndefinedGlobal(slot) => write!(f, "Global slot {slot} is not initialized"),
+            Self::OutOfFuel => write!(f, "Out of fuel"),
+            Self::Cancelled => write!(f, "Execution was cancelled"),
+            Self::TimedOut => write!(f, "Execution timed out"),
+            Self::OutOfMemory => write!(f, "Out of memory"),
+        }
+    }
 }
+
+impl error::Error for RuntimeErrorKind {}
+
+pub type VMResult<T = ()> = Result<T, Box<RuntimeErrorKind>>;
+
+pub(crate) fn vm_error<T>(kind: RuntimeErrorKind) -> VMResult<T> {
+    Err(Box::new(kind))
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub trace: Vec<TokenLocation>,
+}
+
+impl RuntimeError {
+    pub fn location(&self) -> Option<TokenLocation> {
+        self.trace.first().copied()
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl error::Error for RuntimeError {}
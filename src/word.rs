@@ -0,0 +1,204 @@
+use crate::{BinaryOperator, Value};
+
+// Words whose top 14 bits are all set are tagged values, every other bit pattern is a float.
+// Hardware never produces NaNs with this prefix, any that show up are folded into the default NaN.
+const PREFIX: u64 = 0xFFFC_0000_0000_0000;
+const TAG_SHIFT: u32 = 48;
+const PAYLOAD: u64 = (1 << TAG_SHIFT) - 1;
+
+const TAG_SPECIAL: u64 = 0;
+const TAG_INTEGER: u64 = 1;
+const TAG_BOXED: u64 = 2;
+
+const SMALL_MIN: i64 = -(1 << (TAG_SHIFT - 1));
+const SMALL_MAX: i64 = (1 << (TAG_SHIFT - 1)) - 1;
+
+const MIN_HEAP_LIMIT: usize = 1024;
+const MIN_HEAP_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Word(u64);
+
+impl Word {
+    pub(crate) const VOID: Word = Word::tagged(TAG_SPECIAL, 0);
+
+    const fn tagged(tag: u64, payload: u64) -> Self {
+        Self(PREFIX | tag << TAG_SHIFT | payload & PAYLOAD)
+    }
+
+    fn float(value: f64) -> Self {
+        let bits = value.to_bits();
+        if bits & PREFIX == PREFIX {
+            Self(f64::NAN.to_bits())
+        } else {
+            Self(bits)
+        }
+    }
+
+    fn boolean(value: bool) -> Self {
+        Self::tagged(TAG_SPECIAL, value as u64 + 1)
+    }
+
+    pub(crate) fn small(value: i64) -> Option<Self> {
+        (SMALL_MIN..=SMALL_MAX)
+            .contains(&value)
+            .then(|| Self::tagged(TAG_INTEGER, value as u64))
+    }
+
+    fn tag(self) -> Option<u64> {
+        (self.0 & PREFIX == PREFIX).then_some(self.0 >> TAG_SHIFT & 0b11)
+    }
+
+    fn payload(self) -> u64 {
+        self.0 & PAYLOAD
+    }
+
+    pub(crate) fn is_boxed(self) -> bool {
+        self.tag() == Some(TAG_BOXED)
+    }
+
+    fn as_small(self) -> Option<i64> {
+        (self.tag() == Some(TAG_INTEGER)).then_some(((self.0 << 16) as i64) >> 16)
+    }
+
+    fn as_float(self) -> Option<f64> {
+        self.tag().is_none().then_some(f64::from_bits(self.0))
+    }
+
+    // Operations on two small integers or two floats that never leave the word representation.
+    // Anything else, including errors, is left to the Value operations.
+    pub(crate) fn binary(op: BinaryOperator, left: Word, right: Word) -> Option<Word> {
+        if let (Some(l), Some(r)) = (left.as_small(), right.as_small()) {
+            return match op {
+                BinaryOperator::Addict => Word::small(l + r),
+                BinaryOperator::Subtract => Word::small(l - r),
+                BinaryOperator::Multiply => Word::small(l.checked_mul(r)?),
+                BinaryOperator::Divide | BinaryOperator::Modulo if r == 0 => None,
+                BinaryOperator::Divide => Word::small(l / r),
+                BinaryOperator::Modulo => Word::small(l % r),
+                BinaryOperator::Equals => Some(Word::boolean(l == r)),
+                BinaryOperator::NotEquals => Some(Word::boolean(l != r)),
+                BinaryOperator::Less => Some(Word::boolean(l < r)),
+                BinaryOperator::Greater => Some(Word::boolean(l > r)),
+                BinaryOperator::LessEquals => Some(Word::boolean(l <= r)),
+                BinaryOperator::GreaterEquals => Some(Word::boolean(l >= r)),
+            };
+        }
+        let (l, r) = (left.as_float()?, right.as_float()?);
+        Some(match op {
+            BinaryOperator::Addict => Word::float(l + r),
+            BinaryOperator::Subtract => Word::float(l - r),
+            BinaryOperator::Multiply => Word::float(l * r),
+            BinaryOperator::Divide => Word::float(l / r),
+            BinaryOperator::Modulo => Word::float(l % r),
+            BinaryOperator::Equals => Word::boolean(l == r),
+            BinaryOperator::NotEquals => Word::boolean(l != r),
+            BinaryOperator::Less => Word::boolean(l < r),
+            BinaryOperator::Greater => Word::boolean(l > r),
+            BinaryOperator::LessEquals => Word::boolean(l <= r),
+            BinaryOperator::GreaterEquals => Word::boolean(l >= r),
+        })
+    }
+}
+
+// Integers that do not fit in a word live here until the next collection.
+#[derive(Debug)]
+pub(crate) struct Heap {
+    integers: Vec<i64>,
+    limit: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heap {
+    pub(crate) fn new() -> Self {
+        Self {
+            integers: Vec::new(),
+            limit: MIN_HEAP_LIMIT,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.integers.clear();
+        self.limit = MIN_HEAP_LIMIT;
+    }
+
+    pub(crate) fn decode(&self, word: Word) -> Value {
+        match word.tag() {
+            None => Value::Float(f64::from_bits(word.0)),
+            Some(TAG_INTEGER) => Value::Integer(word.as_small().unwrap_or_default()),
+            // Only a register left over from an earlier run can point past the heap.
+            Some(TAG_BOXED) => Value::Integer(
+                self.integers
+                    .get(word.payload() as usize)
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+            Some(_) => match word.payload() {
+                0 => Value::Void,
+                payload => Value::Boolean(payload == 2),
+            },
+        }
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.integers.capacity() * std::mem::size_of::<i64>()
+    }
+
+    // Roots are every word that may still refer to the heap, collection rewrites them in place.
+    // Growing the heap may take at most `budget` more bytes, None means the value does not fit.
+    pub(crate) fn encode(
+        &mut self,
+        value: Value,
+        roots: &mut [Word],
+        budget: usize,
+    ) -> Option<Word> {
+        Some(match value {
+            Value::Void => Word::VOID,
+            Value::Boolean(value) => Word::boolean(value),
+            Value::Float(value) => Word::float(value),
+            Value::Integer(value) => match Word::small(value) {
+                Some(word) => word,
+                None => {
+                    if self.integers.len() >= self.limit {
+                        self.collect(roots);
+                    }
+                    if self.integers.len() == self.integers.capacity() {
+                        let wanted = self.integers.capacity().max(MIN_HEAP_CAPACITY);
+                        let additional = wanted.min(budget / std::mem::size_of::<i64>());
+                        if additional == 0 {
+                            return None;
+                        }
+                        self.integers.reserve_exact(additional);
+                    }
+                    self.integers.push(value);
+                    Word::tagged(TAG_BOXED, self.integers.len() as u64 - 1)
+                }
+            },
+        })
+    }
+
+    fn collect(&mut self, roots: &mut [Word]) {
+        let mut moved = vec![None; self.integers.len()];
+        let mut live = Vec::new();
+        for root in roots.iter_mut().filter(|root| root.is_boxed()) {
+            let index = root.payload() as usize;
+            // Words this run has not written yet may still point into an older heap.
+            let Some(slot) = moved.get_mut(index) else {
+                *root = Word::VOID;
+                continue;
+            };
+            let target = *slot.get_or_insert_with(|| {
+                live.push(self.integers[index]);
+                live.len() as u64 - 1
+            });
+            *root = Word::tagged(TAG_BOXED, target);
+        }
+        self.integers = live;
+        self.limit = MIN_HEAP_LIMIT.max(self.integers.len() * 2);
+    }
+}
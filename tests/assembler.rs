@@ -0,0 +1,121 @@
+use uniq::{Error, MemoryLoader, Program, State, Value};
+
+const SOURCES: &[&str] = &[
+    "",
+    "2 + 3 * 4",
+    "1.5 * 2 - 0.25; 7 % 3 == 1",
+    "true != false; 1 <= 2; 3 >= 4; 5 < 6; 7 > 8",
+    "fn add(a, b) { a + b }\nfn twice(x) { add(x, x) }\ntwice(add(1, 2))",
+    "fn fact(n) { n * 1 }\nlet x = fact(5);\nlet y = x / 2.0;\nx + y",
+    "fn nothing() {}\nnothing(); nothing()",
+    "0 - 9223372036854775807; 123456789.125 * 0.1",
+];
+
+fn compile(source: &str) -> Program {
+    let loader = MemoryLoader::new()
+        .with(
+            "math.uq",
+            "fn square(x) { x * x }\nfn cube(x) { square(x) * x }",
+        )
+        .with("main.uq", source);
+    State::with_loader(loader).compile_path("main.uq").unwrap()
+}
+
+fn round_trip(program: &Program, source: Option<&str>) {
+    let text = program.disassemble(source);
+    let assembled = Program::assemble(&text).unwrap_or_else(|error| panic!("{error}\n{text}"));
+    assert_eq!(&assembled, program, "\n{text}");
+}
+
+#[test]
+fn compiled_programs_round_trip() {
+    for source in SOURCES {
+        let program = compile(source);
+        round_trip(&program, None);
+        round_trip(&program, Some(source));
+    }
+}
+
+#[test]
+fn programs_with_imports_round_trip() {
+    let source = "import \"math.uq\" as math;\nfn f(a, b) { math.cube(a) - b }\nf(2, 1) / 0";
+    let program = compile(source);
+    assert_eq!(program.files().len(), 2);
+    round_trip(&program, Some(source));
+}
+
+#[test]
+fn assembled_programs_run() {
+    let program = Program::assemble(
+        "
+        .function square 1 square
+        .entry start
+
+        square:
+            Multiply r1, r0, r0
+            Return r1
+
+        start:
+            Integer r0, 7
+            Call square, r0   ; calls may name the function
+            Integer r1, 1
+            Subtract r0, r0, r1
+            End r0
+        ",
+    )
+    .unwrap();
+    assert_eq!(program.entry(), 2);
+    assert_eq!(uniq::run(&program).unwrap(), Value::Integer(48));
+}
+
+#[test]
+fn locations_are_kept() {
+    let program = Program::assemble(
+        "
+        Integer r0, 1
+        Integer r1, 0
+        .loc 3:7
+        Divide r0, r0, r1
+        End r0
+        ",
+    )
+    .unwrap();
+    let error = uniq::run(&program).unwrap_err();
+    let location = error.location().unwrap();
+    assert_eq!((location.line, location.column), (2, 6));
+}
+
+fn assemble_error(text: &str) -> (String, u32) {
+    match Program::assemble(text) {
+        Err(Error::Assemble(error)) => (error.message, error.location.line),
+        Err(error) => panic!("unexpected error: {error}"),
+        Ok(_) => panic!("expected an assembly error"),
+    }
+}
+
+#[test]
+fn invalid_assembly_is_reported() {
+    assert_eq!(
+        assemble_error("Integer r0, 1\nJump 3\nEnd r0"),
+        ("Unknown instruction 'Jump'.".to_string(), 1)
+    );
+    assert_eq!(
+        assemble_error(".entry nowhere\nEnd r0"),
+        ("Unknown label 'nowhere'.".to_string(), 0)
+    );
+    assert_eq!(
+        assemble_error("Integer r0, x"),
+        ("Expected integer, found 'x'.".to_string(), 0)
+    );
+    assert_eq!(
+        assemble_error("0000 Void r0\n0002 End r0"),
+        (
+            "Instruction index 2 does not match its position 1.".to_string(),
+            1
+        )
+    );
+    assert_eq!(
+        assemble_error("Return r1, r2"),
+        ("Unexpected 'r2' at end of line.".to_string(), 0)
+    );
+}
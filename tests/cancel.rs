@@ -0,0 +1,85 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+use common::{call_tree, compile};
+use uniq::{CancelToken, Error, Program, RuntimeErrorKind, State, StateOptions, Value};
+
+// 2^40 leaf calls, far more than any test is willing to wait for.
+fn endless() -> Program {
+    compile(&call_tree(40, "x * 3 % 7"))
+}
+
+// The fuel only guards against a hanging test if the interruption is never noticed.
+fn guarded() -> StateOptions {
+    StateOptions::new().with_fuel(1_000_000_000)
+}
+
+fn kind(result: uniq::Result<Value>) -> RuntimeErrorKind {
+    match result {
+        Err(Error::Runtime(error)) => error.kind,
+        result => panic!("expected a runtime error, found {result:?}"),
+    }
+}
+
+#[test]
+fn runs_can_be_cancelled_from_another_thread() {
+    let program = endless();
+    let mut state = State::with_options(guarded());
+    let token = state.cancel_token();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    let start = Instant::now();
+    assert_eq!(kind(state.execute(&program)), RuntimeErrorKind::Cancelled);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    canceller.join().unwrap();
+
+    // The token stays cancelled until it is reset.
+    assert_eq!(kind(state.execute(&program)), RuntimeErrorKind::Cancelled);
+    state.cancel_token().reset();
+    assert_eq!(state.execute(&compile("1 + 2")).unwrap(), Value::Integer(3));
+}
+
+#[test]
+fn tokens_are_shared_between_states() {
+    let token = CancelToken::new();
+    let mut first = State::with_options(guarded());
+    let mut second = State::with_options(guarded());
+    first.set_cancel_token(token.clone());
+    second.set_cancel_token(token.clone());
+    token.cancel();
+    assert!(first.cancel_token().is_cancelled());
+    assert_eq!(kind(first.execute(&endless())), RuntimeErrorKind::Cancelled);
+    assert_eq!(
+        kind(second.execute(&endless())),
+        RuntimeErrorKind::Cancelled
+    );
+}
+
+#[test]
+fn runs_time_out() {
+    let program = endless();
+    let timeout = Duration::from_millis(50);
+    let mut state = State::with_options(guarded().with_timeout(timeout));
+    let start = Instant::now();
+    assert_eq!(kind(state.execute(&program)), RuntimeErrorKind::TimedOut);
+    assert!(start.elapsed() >= timeout);
+    assert!(state.deadline().unwrap() <= Instant::now());
+
+    // A resumed run needs a new deadline, without one it times out again right away.
+    assert_eq!(kind(state.resume(&program)), RuntimeErrorKind::TimedOut);
+    state.set_deadline(Some(Instant::now() + timeout));
+    assert_eq!(kind(state.resume(&program)), RuntimeErrorKind::TimedOut);
+}
+
+#[test]
+fn short_runs_finish_before_the_deadline() {
+    let program = compile("fn f(x) { x * 2 }\nf(21)");
+    let mut state = State::with_options(StateOptions::new().with_timeout(Duration::from_secs(60)));
+    assert_eq!(state.execute(&program).unwrap(), Value::Integer(42));
+}
@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+// Writes `source` to a script file named after the test, so tests can run in parallel.
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("uniq-cli-{}-{name}.uq", std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn uniq(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_uniq"))
+        .args(arguments)
+        .output()
+        .unwrap()
+}
+
+fn run(name: &str, source: &str) -> (Option<i32>, String, String) {
+    let path = script(name, source);
+    let output = uniq(&["run", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // The script's path differs between runs, leave only its file name.
+    let stderr = String::from_utf8(output.stderr)
+        .unwrap()
+        .replace(path.to_str().unwrap(), "script.uq");
+    (output.status.code(), stdout, stderr)
+}
+
+#[test]
+fn successful_runs_print_the_result() {
+    assert_eq!(
+        run("success", "fn square(x) { x * x }\nsquare(7)\n"),
+        (Some(0), "49\n".to_string(), String::new())
+    );
+}
+
+#[test]
+fn syntax_errors_exit_with_4() {
+    assert_eq!(
+        run("parse", "let a = 1;\nlet b = (a +;\n"),
+        (
+            Some(4),
+            String::new(),
+            "\
+error[E0002]: Expected value, found ';'.
+ --> script.uq:2:13
+  |
+1 | let a = 1;
+2 | let b = (a +;
+  |             ^
+"
+            .to_string()
+        )
+    );
+}
+
+#[test]
+fn compile_errors_exit_with_5() {
+    let (code, stdout, stderr) = run("compile", "missing(1)\n");
+    assert_eq!((code, stdout.as_str()), (Some(5), ""));
+    assert!(stderr.starts_with("error[E0003]: "), "{stderr}");
+    assert!(stderr.contains(" --> script.uq:1:1\n"), "{stderr}");
+}
+
+#[test]
+fn runtime_errors_exit_with_6() {
+    assert_eq!(
+        run("runtime", "let a = 1;\na / 0\n"),
+        (
+            Some(6),
+            String::new(),
+            "\
+error[E0100]: Dividing by zero.
+ --> script.uq:2:3
+  |
+1 | let a = 1;
+2 | a / 0
+  |   ^
+  = hint: make sure the divisor is never zero
+"
+            .to_string()
+        )
+    );
+}
+
+#[test]
+fn usage_and_io_errors() {
+    let output = uniq(&["bogus"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Unknown command or arguments for 'bogus'.\n\nUsage: uniq"));
+
+    let output = uniq(&["run", "definitely/missing.uq"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error[E0200]: "));
+}
+
+#[test]
+fn breakpoints_can_name_the_debugged_script() {
+    let path = script("debug", "fn add(x, y) {\n  x + y\n}\nadd(2, 3)\n");
+    let path = path.to_str().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_uniq"))
+        .args(["debug", path])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = format!("break {path}:2\ncontinue\nquit\n");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.contains(&format!("{path}:2:5\n")), "{stdout}");
+    assert!(!stdout.contains("No code starts"), "{stdout}");
+}
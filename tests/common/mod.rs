@@ -0,0 +1,26 @@
+// Every test crate that includes this module uses only some of the helpers.
+#![allow(dead_code)]
+
+use uniq::{CompileOptions, Program};
+
+pub fn compile(source: &str) -> Program {
+    compile_with_level(source, CompileOptions::new().optimization())
+}
+
+pub fn compile_with_level(source: &str, level: u8) -> Program {
+    let ast = uniq::parse(source.as_bytes()).unwrap();
+    uniq::compile_with_options(&ast, CompileOptions::new().with_optimization(level)).unwrap()
+}
+
+// Every level calls the one below twice, so a run makes 2^depth leaf calls.
+pub fn call_tree(depth: usize, leaf: &str) -> String {
+    let mut source = format!("fn f0(x) {{ {leaf} }}\n");
+    for level in 1..=depth {
+        let below = level - 1;
+        source.push_str(&format!(
+            "fn f{level}(x) {{\n  f{below}(x + 1) + f{below}(x * 2)\n}}\n"
+        ));
+    }
+    source.push_str(&format!("f{depth}(1)\n"));
+    source
+}
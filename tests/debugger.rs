@@ -0,0 +1,232 @@
+use uniq::{Debugger, MemoryLoader, State, StepResult, Value};
+
+// Lines below count from zero, like `TokenLocation::line`.
+const SOURCE: &str = "\
+fn add(x, y) {
+  x + y
+}
+fn twice(x) {
+  add(x, x) * 2
+}
+let a = add(2, 3);
+let b = twice(a);
+a * b
+";
+
+fn debugger() -> Debugger {
+    Debugger::new("script.uq", SOURCE).unwrap()
+}
+
+fn line(debugger: &Debugger) -> u32 {
+    debugger.location().unwrap().line
+}
+
+fn locals(debugger: &Debugger) -> Vec<String> {
+    debugger
+        .locals()
+        .into_iter()
+        .map(|(name, value)| format!("{name} = {value}"))
+        .collect()
+}
+
+#[test]
+fn breakpoints_pause_every_time_their_line_is_entered() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    assert!(debugger.set_breakpoint(file, 1));
+    assert!(matches!(debugger.resume(), StepResult::Paused));
+    assert_eq!(line(&debugger), 1);
+    assert_eq!(locals(&debugger), ["x = 2", "y = 3"]);
+
+    assert!(matches!(debugger.resume(), StepResult::Paused));
+    assert_eq!(debugger.state().frames().len(), 2);
+    assert_eq!(locals(&debugger), ["x = 5", "y = 5"]);
+
+    assert!(debugger.remove_breakpoint(file, 1));
+    assert!(debugger.breakpoints().is_empty());
+    assert!(matches!(
+        debugger.resume(),
+        StepResult::Finished(Value::Integer(100))
+    ));
+}
+
+#[test]
+fn breakpoints_need_code_on_their_line() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    assert!(!debugger.set_breakpoint(file, 2));
+    assert!(!debugger.set_breakpoint(file, 40));
+    assert!(debugger.breakpoints().is_empty());
+}
+
+#[test]
+fn returning_into_a_breakpoint_line_does_not_pause_again() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    assert!(debugger.set_breakpoint(file, 7));
+    assert!(matches!(debugger.resume(), StepResult::Paused));
+    assert_eq!(line(&debugger), 7);
+    assert!(matches!(
+        debugger.resume(),
+        StepResult::Finished(Value::Integer(100))
+    ));
+
+    debugger.restart().unwrap();
+    assert!(matches!(debugger.resume(), StepResult::Paused));
+    assert_eq!(line(&debugger), 7);
+}
+
+#[test]
+fn stepping_follows_source_lines() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    debugger.set_breakpoint(file, 7);
+    debugger.resume();
+
+    // Into `twice`, then into `add`, then back out to `twice` and to the script.
+    assert!(matches!(debugger.step_into(), StepResult::Paused));
+    assert_eq!((line(&debugger), debugger.state().frames().len()), (4, 1));
+    assert!(matches!(debugger.step_into(), StepResult::Paused));
+    assert_eq!((line(&debugger), debugger.state().frames().len()), (1, 2));
+    assert!(matches!(debugger.step_out(), StepResult::Paused));
+    assert_eq!((line(&debugger), debugger.state().frames().len()), (4, 1));
+    assert!(matches!(debugger.step_out(), StepResult::Paused));
+    assert_eq!((line(&debugger), debugger.state().frames().len()), (7, 0));
+
+    assert!(matches!(debugger.step_over(), StepResult::Paused));
+    assert_eq!(line(&debugger), 8);
+    assert!(matches!(
+        debugger.step_over(),
+        StepResult::Finished(Value::Integer(100))
+    ));
+}
+
+#[test]
+fn stepping_over_skips_calls() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    debugger.set_breakpoint(file, 6);
+    debugger.resume();
+    assert!(matches!(debugger.step_over(), StepResult::Paused));
+    assert_eq!((line(&debugger), debugger.state().frames().len()), (7, 0));
+    assert_eq!(locals(&debugger), ["a = 5"]);
+}
+
+#[test]
+fn expressions_see_the_paused_frame() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    debugger.set_breakpoint(file, 4);
+    debugger.resume();
+    assert_eq!(
+        debugger.evaluate("x * 10 + add(a, 1)").unwrap(),
+        Value::Integer(56)
+    );
+    assert_eq!(debugger.stack()[0], Value::Integer(5));
+    assert!(debugger.evaluate("b").is_err());
+    assert!(debugger.evaluate("y").is_err());
+
+    // Evaluating does not change the paused run.
+    debugger.evaluate("let a = 1000;\na").unwrap();
+    assert!(matches!(
+        debugger.resume(),
+        StepResult::Finished(Value::Integer(100))
+    ));
+}
+
+#[test]
+fn expressions_share_one_source_file() {
+    let mut debugger = debugger();
+    debugger.evaluate("1").unwrap();
+    let files = debugger.state().sources().files().count();
+    for value in 0..20 {
+        debugger.evaluate(&format!("a + {value}")).unwrap_err();
+    }
+    assert_eq!(debugger.state().sources().files().count(), files);
+}
+
+#[test]
+fn watch_errors_show_their_own_source() {
+    let mut debugger = debugger();
+    let input = "break 8\nwatch a / 0\nwatch a + true\nwatch a\ncontinue\nquit\n";
+    let mut out = Vec::new();
+    debugger.run(input.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("0: a / 0 =\nerror[E0100]"), "{out}");
+    assert!(out.contains("<watch>:1:3\n  |\n1 | a / 0\n"), "{out}");
+    assert!(out.contains("<watch>:1:3\n  |\n1 | a + true\n"), "{out}");
+    assert!(out.contains("2: a = 5\n"), "{out}");
+}
+
+#[test]
+fn watches_are_evaluated_in_order() {
+    let mut debugger = debugger();
+    let file = debugger.file();
+    debugger.set_breakpoint(file, 8);
+    debugger.watch("a + b");
+    debugger.watch("missing");
+    debugger.watch("a");
+    debugger.resume();
+    let watches = debugger.watches();
+    assert_eq!(watches.len(), 3);
+    assert_eq!(watches[0].0, "a + b");
+    assert_eq!(*watches[0].1.as_ref().unwrap(), Value::Integer(25));
+    assert!(watches[1].1.is_err());
+
+    assert!(debugger.unwatch(1));
+    assert!(!debugger.unwatch(5));
+    let values: Vec<_> = debugger
+        .watches()
+        .into_iter()
+        .map(|(_, value)| value.unwrap())
+        .collect();
+    assert_eq!(values, [Value::Integer(25), Value::Integer(5)]);
+}
+
+#[test]
+fn breakpoints_work_in_imported_modules() {
+    let loader = MemoryLoader::new().with("math.uq", "fn square(x) {\n  x * x\n}\n");
+    let source = "import \"math.uq\" as math;\nmath.square(7)\n";
+    let mut debugger = Debugger::with_state(State::with_loader(loader), "main.uq", source).unwrap();
+    let (module, _) = debugger
+        .state()
+        .sources()
+        .files()
+        .find(|(_, file)| file.name() == "math.uq")
+        .unwrap();
+    assert_ne!(module, debugger.file());
+    assert!(debugger.set_breakpoint(module, 1));
+    assert!(matches!(debugger.resume(), StepResult::Paused));
+    assert_eq!(debugger.location().unwrap().file, module);
+    assert_eq!(locals(&debugger), ["x = 7"]);
+    assert_eq!(debugger.backtrace().len(), 2);
+}
+
+#[test]
+fn breakpoints_by_name_use_the_loaded_script() {
+    let mut state = State::new();
+    state.compile("script.uq", "1").unwrap();
+    let mut debugger = Debugger::with_state(state, "script.uq", SOURCE).unwrap();
+    debugger.load("script.uq", SOURCE).unwrap();
+    let mut out = Vec::new();
+    debugger
+        .run("break script.uq:2\nc\nquit\n".as_bytes(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("script.uq:2:5\n   2 |   x + y\n"), "{out}");
+    assert_eq!(debugger.breakpoints(), [(debugger.file(), 1)]);
+}
+
+#[test]
+fn interactive_session() {
+    let mut debugger = debugger();
+    let input = "break 2\ncontinue\nlocals\nprint x * y\nwhere\ndelete 2\nc\nquit\nc\n";
+    let mut out = Vec::new();
+    debugger.run(input.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("script.uq:2:5\n   2 |   x + y\n"), "{out}");
+    assert!(out.contains("x = 2\ny = 3\n"), "{out}");
+    assert!(out.contains("(debug) 6\n"), "{out}");
+    assert!(out.contains("script.uq:7:9\n"), "{out}");
+    assert_eq!(out.matches("Finished with 100").count(), 1, "{out}");
+}
@@ -0,0 +1,211 @@
+use uniq::{Diagnostic, FileId, Renderer, SourceMap, TokenLocation};
+
+fn location(file: FileId, offset: u32, line: u32, column: u32, length: u32) -> TokenLocation {
+    TokenLocation {
+        file,
+        offset,
+        line,
+        column,
+        length,
+    }
+}
+
+fn render(renderer: Renderer, diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let mut out = Vec::new();
+    renderer.render(&mut out, diagnostic, sources).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn plain(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    render(Renderer::new().with_color(false), diagnostic, sources)
+}
+
+#[test]
+fn plain_output_has_no_escape_codes() {
+    let mut sources = SourceMap::new();
+    let file = sources.add(
+        "main.uq".to_string(),
+        "let a = 1;\nlet b = a / 0;\na + b\n".to_string(),
+    );
+    let diagnostic = Diagnostic::error(
+        "E0100",
+        "Division by zero".to_string(),
+        Some(location(file, 19, 1, 8, 5)),
+    )
+    .with_hint("make sure the divisor is never zero".to_string())
+    .with_note("the divisor is a literal".to_string());
+    assert_eq!(
+        plain(&diagnostic, &sources),
+        "\
+error[E0100]: Division by zero
+ --> main.uq:2:9
+  |
+1 | let a = 1;
+2 | let b = a / 0;
+  |         ^^^^^
+3 | a + b
+  = hint: make sure the divisor is never zero
+  = note: the divisor is a literal
+"
+    );
+    let colored = render(Renderer::new(), &diagnostic, &sources);
+    assert!(colored.starts_with("\x1b[1;31merror[E0100]\x1b[0m\x1b[1m: Division by zero\x1b[0m\n"));
+    assert!(colored.contains("\x1b[1;31m^^^^^\x1b[0m"));
+}
+
+#[test]
+fn tabs_expand_in_lines_and_markers() {
+    let mut sources = SourceMap::new();
+    let file = sources.add(
+        "tabs.uq".to_string(),
+        "fn f(x) {\n\tx +\t\ttrue\n}\n".to_string(),
+    );
+    let diagnostic = Diagnostic::error(
+        "E0101",
+        "Unable to add integer and boolean.".to_string(),
+        Some(location(file, 11, 1, 1, 9)),
+    );
+    assert_eq!(
+        plain(&diagnostic, &sources),
+        "\
+error[E0101]: Unable to add integer and boolean.
+ --> tabs.uq:2:2
+  |
+1 | fn f(x) {
+2 |     x +     true
+  |     ^^^^^^^^^^^^
+3 | }
+"
+    );
+    let renderer = Renderer::new().with_color(false).with_tab_width(2);
+    assert!(render(renderer, &diagnostic, &sources).contains(
+        "\
+2 |   x +   true
+  |   ^^^^^^^^^^
+"
+    ));
+}
+
+#[test]
+fn spans_continue_over_lines_with_context() {
+    let mut sources = SourceMap::new();
+    let file = sources.add(
+        "multi.uq".to_string(),
+        "let a = 1;\nlet b = (a +\n  2) *\n  3;\nb\n".to_string(),
+    );
+    let diagnostic = Diagnostic::error(
+        "E0003",
+        "Expression is too long.".to_string(),
+        Some(location(file, 19, 1, 8, 16)),
+    );
+    assert_eq!(
+        plain(&diagnostic, &sources),
+        "\
+error[E0003]: Expression is too long.
+ --> multi.uq:2:9
+  |
+1 | let a = 1;
+2 | let b = (a +
+  |         ^^^^
+3 |   2) *
+  | ^^^^^^
+4 |   3;
+  | ^^^^
+5 | b
+"
+    );
+    let renderer = Renderer::new().with_color(false).with_context(0);
+    assert_eq!(
+        render(renderer, &diagnostic, &sources),
+        "\
+error[E0003]: Expression is too long.
+ --> multi.uq:2:9
+  |
+2 | let b = (a +
+  |         ^^^^
+3 |   2) *
+  | ^^^^^^
+4 |   3;
+  | ^^^^
+"
+    );
+    // More context than there are lines stops at the last one.
+    let renderer = Renderer::new().with_color(false).with_context(3);
+    assert!(render(renderer, &diagnostic, &sources).ends_with("  | ^^^^\n5 | b\n"));
+}
+
+#[test]
+fn errors_at_the_end_of_input_point_at_the_last_line() {
+    let mut sources = SourceMap::new();
+    let file = sources.add(
+        "main.uq".to_string(),
+        "let a = 1;\nlet b = a +\n".to_string(),
+    );
+    let Err(error) = uniq::parse_file(&sources, file) else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(
+        plain(&error.diagnostic(), &sources),
+        "\
+error[E0002]: Expected value, found end.
+ --> main.uq:3:1
+  |
+2 | let b = a +
+3 | 
+  | ^
+"
+    );
+
+    // Locations past the end are clamped, the header agrees with the snippet.
+    let diagnostic = Diagnostic::error(
+        "E0003",
+        "Out of range.".to_string(),
+        Some(location(file, 40, 9, 0, 1)),
+    );
+    assert_eq!(
+        plain(&diagnostic, &sources),
+        "\
+error[E0003]: Out of range.
+ --> main.uq:2:12
+  |
+1 | let a = 1;
+2 | let b = a +
+  |            ^
+"
+    );
+}
+
+#[test]
+fn repeated_frames_are_folded() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("rec.uq".to_string(), "fn f(x) { f(x) }\nf(1)\n".to_string());
+    let inner = location(file, 10, 0, 10, 4);
+    let outer = location(file, 17, 1, 0, 4);
+    let diagnostic = Diagnostic::error("E0102", "Stack overflow".to_string(), Some(inner))
+        .with_trace(vec![inner, inner, inner, inner, outer]);
+    assert_eq!(
+        plain(&diagnostic, &sources),
+        "\
+error[E0102]: Stack overflow
+ --> rec.uq:1:11
+  |
+1 | fn f(x) { f(x) }
+  |           ^^^^
+2 | f(1)
+stack backtrace:
+   0: rec.uq:1:11 `f(x)`
+      [previous frame repeated 3 more times]
+   4: rec.uq:2:1 `f(1)`
+"
+    );
+
+    // A fold at the very end is closed too.
+    let diagnostic = diagnostic.with_trace(vec![outer, inner, inner]);
+    assert!(plain(&diagnostic, &sources).ends_with(
+        "\
+   0: rec.uq:2:1 `f(1)`
+   1: rec.uq:1:11 `f(x)`
+      [previous frame repeated 1 more times]
+"
+    ));
+}
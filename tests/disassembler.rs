@@ -0,0 +1,50 @@
+const SOURCE: &str = "fn add(x, y) {\n  x + y\n}\nlet a = add(1, 2);\na * 3\n";
+
+const LISTING: &str = "\
+.entry @main
+.function add 2 @f0
+
+@main:  ; main
+    0000  Integer r0, 1
+    0001  Integer r1, 2
+;    4 | let a = add(1, 2);
+    .loc 4:9 offset=33 length=3
+    .parent 3
+    0002  Call 0, r0  ; add
+    .loc 4:1 offset=25 length=3
+    0003  SetGlobal 0, r0
+;    5 | a * 3
+    .loc 5:1 offset=44 length=1
+    .parent 5
+    0004  Global r0, 0
+    .loc 5:3 offset=46 length=1
+    0005  MultiplyConst r0, r0, 3
+    0006  End r0
+
+@f0:  ; fn add/2
+;    2 |   x + y
+    .loc 2:5 offset=19 length=1
+    0007  Addict r2, r0, r1
+    0008  Return r2
+";
+
+fn header() -> String {
+    format!(".version \"{}\"\n", env!("CARGO_PKG_VERSION"))
+}
+
+#[test]
+fn listings_interleave_the_source_lines() {
+    let program = uniq::parse_and_compile(SOURCE.as_bytes()).unwrap();
+    assert_eq!(program.disassemble(Some(SOURCE)), header() + LISTING);
+}
+
+#[test]
+fn listings_without_source_keep_the_locations() {
+    let program = uniq::parse_and_compile(SOURCE.as_bytes()).unwrap();
+    let expected: String = LISTING
+        .lines()
+        .filter(|line| !line.starts_with(';'))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert_eq!(program.disassemble(None), header() + &expected);
+}
@@ -0,0 +1,50 @@
+use std::{error, io};
+
+use uniq::Error;
+
+#[test]
+fn messages_follow_the_phase_and_location() {
+    let Err(error) = uniq::parse(b"1 +\n* 2") else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(
+        error.to_string(),
+        "Parsing error at line 2, column 1: Expected value, found '*'."
+    );
+
+    let program = uniq::parse_and_compile(b"let a = 1;\na / 0").unwrap();
+    assert_eq!(
+        uniq::run(&program).unwrap_err().to_string(),
+        "Runtime error at line 2, column 3: Dividing by zero."
+    );
+
+    let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+    assert_eq!(error.to_string(), "I/O error: no such file");
+}
+
+// Formats an error the way reporters do, with every source on its own line.
+fn chain(error: &dyn error::Error) -> String {
+    let mut text = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        text.push_str(&format!("\ncaused by: {error}"));
+        source = error.source();
+    }
+    text
+}
+
+#[test]
+fn chains_name_every_message_once() {
+    let program = uniq::parse_and_compile(b"1 / 0").unwrap();
+    assert_eq!(
+        chain(&uniq::run(&program).unwrap_err()),
+        "Runtime error at line 1, column 3: Dividing by zero."
+    );
+    let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+    assert_eq!(chain(&error), "I/O error: no such file");
+    let error = uniq::Program::from_bytes(b"UNIQ", uniq::Encoding::Binary).unwrap_err();
+    assert_eq!(
+        chain(&error),
+        "Bytecode format error: Not a uniq bytecode file, the 'UNIQ' header is missing"
+    );
+}
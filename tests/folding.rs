@@ -0,0 +1,62 @@
+mod common;
+
+use common::compile_with_level;
+use uniq::{Error, Instruction, Value};
+
+const EXPRESSIONS: &[&str] = &[
+    "60 * 60 * 24",
+    "7 / 2 + 7 % 2 - 1.5",
+    "9223372036854775807 + 1",
+    "1 / 2.0 * 4 == 2",
+    "(3 + 1) * 2 >= 8",
+    "2.5 % 1 != 0.5",
+    "0.0 / 0.0 == 0.0 / 0.0",
+    "fn f(x) { x * (2 + 3) }\nf(4) - 10 * 2",
+];
+
+#[test]
+fn folding_preserves_results() {
+    for source in EXPRESSIONS {
+        let folded = uniq::run(&compile_with_level(source, 1)).unwrap();
+        let plain = uniq::run(&compile_with_level(source, 0)).unwrap();
+        match (folded, plain) {
+            (Value::Float(folded), Value::Float(plain)) => {
+                assert_eq!(folded.to_bits(), plain.to_bits(), "{source}")
+            }
+            (folded, plain) => assert_eq!(folded, plain, "{source}"),
+        }
+    }
+}
+
+#[test]
+fn constant_expressions_become_one_instruction() {
+    let program = compile_with_level("60 * 60 * 24", 1);
+    assert_eq!(
+        program.instructions(),
+        &[Instruction::Integer(0, 86400), Instruction::End(0)]
+    );
+    // The constant points at the outermost operator it replaces.
+    let locations: Vec<_> = program
+        .line_table()
+        .entries()
+        .map(|(index, location)| (index, location.offset))
+        .collect();
+    assert_eq!(locations, [(0, 8)]);
+    assert_eq!(
+        compile_with_level("60 * 60 * 24", 0).instructions().len(),
+        6
+    );
+}
+
+#[test]
+fn failing_operations_stay_runtime_errors() {
+    for source in ["1 + 2 / (3 - 3)", "1 + (true + 2)"] {
+        let folded = uniq::run(&compile_with_level(source, 1)).unwrap_err();
+        let plain = uniq::run(&compile_with_level(source, 0)).unwrap_err();
+        let (Error::Runtime(folded), Error::Runtime(plain)) = (folded, plain) else {
+            panic!("expected runtime errors");
+        };
+        assert_eq!(folded.kind, plain.kind, "{source}");
+        assert_eq!(folded.location(), plain.location(), "{source}");
+    }
+}
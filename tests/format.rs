@@ -0,0 +1,295 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use serde::Serialize;
+use uniq::{
+    BinaryOperator, Encoding, Error, FileId, FormatError, FunctionInfo, Instruction, Payload,
+    Program, TokenLocation, Value, FORMAT_VERSION, MAGIC,
+};
+
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("uniq-format-{}-{name}", std::process::id()))
+}
+
+fn program() -> Program {
+    uniq::parse_and_compile(b"fn f(x) { x * 2 }\nf(21)").unwrap()
+}
+
+#[test]
+fn binary_files_start_with_a_header() {
+    let path = temp("header.uqb");
+    program().save_bin(path.to_str().unwrap()).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    let loaded = Program::load_bin(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(&bytes[..4], &MAGIC);
+    assert_eq!(&bytes[4..8], &FORMAT_VERSION.to_le_bytes());
+    assert_eq!(loaded.unwrap(), program());
+}
+
+#[test]
+fn json_files_carry_the_format() {
+    let path = temp("header.json");
+    program().save_json(path.to_str().unwrap(), false).unwrap();
+    let value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let loaded = Program::load_json(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(value["magic"], "UNIQ");
+    assert_eq!(value["format"], FORMAT_VERSION);
+    assert_eq!(loaded.unwrap(), program());
+}
+
+fn load_bin_bytes(name: &str, bytes: &[u8]) -> Result<Program, Error> {
+    let path = temp(name);
+    fs::write(&path, bytes).unwrap();
+    let result = Program::load_bin(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    result
+}
+
+#[test]
+fn newer_formats_are_rejected() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    bytes.extend_from_slice(&[0; 16]);
+    let error = load_bin_bytes("newer.uqb", &bytes).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::Format(FormatError::Unsupported { found, supported })
+            if found == FORMAT_VERSION + 1 && supported == FORMAT_VERSION
+    ));
+}
+
+#[test]
+fn garbage_is_rejected() {
+    let error = load_bin_bytes("garbage.uqb", b"definitely not bytecode").unwrap_err();
+    assert!(matches!(error, Error::Format(FormatError::BadMagic)));
+}
+
+// Instruction set of formats 0 to 3, in declaration order so bincode picks the same tags.
+#[allow(dead_code)]
+#[derive(Serialize)]
+enum StackInstruction {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Void,
+    Pop,
+    Addict,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equals,
+    NotEquals,
+    Less,
+    Greater,
+    LessEquals,
+    GreaterEquals,
+    Local(u32),
+    Global(u32),
+    SetGlobal(u32),
+    Call(u32),
+    Return,
+    End,
+}
+
+// Layout of format 1, which kept locations in a map keyed by instruction index.
+#[derive(Serialize)]
+struct ProgramV1 {
+    version: &'static str,
+    entry: usize,
+    instructions: Vec<StackInstruction>,
+    locations: HashMap<usize, TokenLocation>,
+    parents: HashMap<usize, usize>,
+    functions: Vec<FunctionInfo>,
+    files: Vec<String>,
+}
+
+// fn f(x) { x * 2 }
+// true; f(21) < 50
+fn legacy() -> ProgramV1 {
+    use StackInstruction::*;
+    let location = TokenLocation {
+        file: FileId::default(),
+        offset: 12,
+        line: 0,
+        column: 12,
+        length: 1,
+    };
+    ProgramV1 {
+        version: "0.1.0",
+        entry: 4,
+        instructions: vec![
+            Local(0),
+            Integer(2),
+            Multiply,
+            Return,
+            Boolean(true),
+            Pop,
+            Integer(21),
+            Call(0),
+            Integer(50),
+            Less,
+            End,
+        ],
+        locations: HashMap::from([(2, location)]),
+        parents: HashMap::new(),
+        functions: vec![FunctionInfo {
+            name: "f".to_string(),
+            arity: 1,
+            entry: 0,
+        }],
+        files: vec!["legacy.uq".to_string()],
+    }
+}
+
+#[test]
+fn legacy_files_are_migrated() {
+    let payload = bincode::serialize(&legacy()).unwrap();
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    let program = load_bin_bytes("v1.uqb", &bytes).unwrap();
+    assert_eq!(
+        program.instructions(),
+        &[
+            Instruction::Move(1, 0),
+            Instruction::Integer(2, 2),
+            Instruction::Binary(BinaryOperator::Multiply, 1, 1, 2),
+            Instruction::Return(1),
+            Instruction::Boolean(0, true),
+            Instruction::Integer(0, 21),
+            Instruction::Call(0, 0),
+            Instruction::Integer(1, 50),
+            Instruction::Binary(BinaryOperator::Less, 0, 0, 1),
+            Instruction::End(0),
+        ]
+    );
+    assert_eq!(
+        program.location(2).map(|location| location.offset),
+        Some(12)
+    );
+    assert!(program.verify().is_ok());
+    assert_eq!(uniq::run(&program).unwrap(), Value::Boolean(true));
+
+    let mut value = serde_json::to_value(legacy()).unwrap();
+    value["magic"] = "UNIQ".into();
+    value["format"] = 1.into();
+    let json = serde_json::to_vec(&value).unwrap();
+    assert_eq!(Program::from_bytes(&json, Encoding::Json).unwrap(), program);
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+// The fixtures were written by the 0.1.0 release, which had no header.
+// 1 + 2 * 3.5 -
+//   10 % 4 < 5
+#[test]
+fn files_of_the_first_release_are_migrated() {
+    let program = Program::load_bin(&fixture("arithmetic-0.1.0.uqb")).unwrap();
+    assert_eq!(uniq::run(&program).unwrap(), Value::Boolean(false));
+    assert_eq!(
+        Program::load_json(&fixture("arithmetic-0.1.0.json")).unwrap(),
+        program
+    );
+    let location = program.location(program.instructions().len() - 2).unwrap();
+    assert_eq!((location.line, location.column), (1, 9));
+}
+
+// 7 +
+//   1 / 0
+#[test]
+fn stack_code_of_the_first_release_is_translated_to_registers() {
+    let program = Program::load_bin(&fixture("division-0.1.0.uqb")).unwrap();
+    assert_eq!(
+        program.instructions(),
+        &[
+            Instruction::Integer(0, 7),
+            Instruction::Integer(1, 1),
+            Instruction::Integer(2, 0),
+            Instruction::Binary(BinaryOperator::Divide, 1, 1, 2),
+            Instruction::Binary(BinaryOperator::Addict, 0, 0, 1),
+            Instruction::End(0),
+        ]
+    );
+    assert_eq!(program.location(3).map(|location| location.line), Some(1));
+}
+
+#[test]
+fn errors_in_files_of_the_first_release_keep_their_location() {
+    let programs = [
+        Program::load_bin(&fixture("division-0.1.0.uqb")),
+        Program::load_json(&fixture("division-0.1.0.json")),
+    ];
+    for program in programs {
+        let error = uniq::run(&program.unwrap()).unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!((location.line, location.column), (1, 4));
+    }
+}
+
+#[test]
+fn json_line_tables_are_readable() {
+    let value: serde_json::Value =
+        serde_json::from_slice(&program().to_bytes(Encoding::Json).unwrap()).unwrap();
+    let first = &value["locations"][0];
+    assert!(first["index"].is_u64());
+    assert!(first["line"].is_u64());
+    assert!(first["column"].is_u64());
+}
+
+#[test]
+fn custom_migration_hooks_are_used() {
+    fn hook(format: u32, payload: Payload) -> uniq::Result<Program> {
+        match (format, payload) {
+            (7, Payload::Binary(bytes)) => Ok(bincode::deserialize(&bytes[1..])?),
+            (format, payload) => uniq::migrate(format, payload),
+        }
+    }
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&7u32.to_le_bytes());
+    bytes.push(0xff);
+    bytes.extend_from_slice(&bincode::serialize(&program()).unwrap());
+    let path = temp("custom.uqb");
+    fs::write(&path, bytes).unwrap();
+    let result = Program::load_bin_with(path.to_str().unwrap(), hook);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(result.unwrap(), program());
+}
+
+#[test]
+fn programs_round_trip_through_memory() {
+    for encoding in [Encoding::Binary, Encoding::Json, Encoding::PrettyJson] {
+        let bytes = program().to_bytes(encoding).unwrap();
+        assert_eq!(Program::from_bytes(&bytes, encoding).unwrap(), program());
+        let mut reader = io::Cursor::new(bytes);
+        assert_eq!(
+            Program::read_from(&mut reader, encoding).unwrap(),
+            program()
+        );
+    }
+}
+
+#[test]
+fn writers_receive_the_same_bytes_as_files() {
+    let program = program();
+    let path = temp("writer.uqb");
+    program.save_bin(path.to_str().unwrap()).unwrap();
+    let file = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let mut written = Vec::new();
+    program.write_to(&mut written, Encoding::Binary).unwrap();
+    assert_eq!(written, file);
+}
+
+#[test]
+fn truncated_input_is_an_error() {
+    let bytes = program().to_bytes(Encoding::Binary).unwrap();
+    let error = Program::from_bytes(&bytes[..bytes.len() / 2], Encoding::Binary).unwrap_err();
+    assert!(matches!(error, Error::Bincode(_)));
+    let error = Program::from_bytes(b"{\"magic\": \"UNIQ\"", Encoding::Json).unwrap_err();
+    assert!(matches!(error, Error::Json(_)));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{call_tree, compile};
+use uniq::{Error, RuntimeErrorKind, State, StateOptions, Value};
+
+fn out_of_fuel(result: &uniq::Result<Value>) -> bool {
+    matches!(result, Err(Error::Runtime(error)) if error.kind == RuntimeErrorKind::OutOfFuel)
+}
+
+#[test]
+fn runs_without_fuel_are_unlimited() {
+    let mut state = State::new();
+    assert!(state.execute(&compile(&call_tree(8, "x % 7"))).is_ok());
+    assert_eq!(state.remaining_fuel(), None);
+    state.refuel(10);
+    assert_eq!(state.remaining_fuel(), None);
+}
+
+#[test]
+fn every_step_takes_fuel() {
+    let mut state = State::with_options(StateOptions::new().with_fuel(100));
+    assert_eq!(state.execute(&compile("1 + 2")).unwrap(), Value::Integer(3));
+    assert_eq!(state.remaining_fuel(), Some(98));
+    let mut state = State::with_options(StateOptions::new().with_fuel(0));
+    assert!(out_of_fuel(&state.execute(&compile("1 + 2"))));
+}
+
+#[test]
+fn running_dry_reports_the_location() {
+    let program = compile(&call_tree(10, "x % 7"));
+    let mut state = State::with_options(StateOptions::new().with_fuel(1000));
+    let result = state.execute(&program);
+    assert!(out_of_fuel(&result));
+    assert_eq!(state.remaining_fuel(), Some(0));
+    let Err(error) = result else { unreachable!() };
+    let location = program.location(state.program_counter());
+    assert!(location.is_some());
+    assert_eq!(error.location(), location);
+}
+
+#[test]
+fn refueled_runs_resume_where_they_stopped() {
+    let program = compile(&call_tree(10, "x % 7"));
+    let expected = uniq::run(&program).unwrap();
+    let mut state = State::with_options(StateOptions::new().with_fuel(500));
+    let mut result = state.execute(&program);
+    let mut stops = 0;
+    while out_of_fuel(&result) {
+        stops += 1;
+        state.refuel(500);
+        result = state.resume(&program);
+    }
+    assert!(stops > 1);
+    assert_eq!(result.unwrap(), expected);
+
+    // A new run starts over with the configured budget.
+    assert!(out_of_fuel(&state.execute(&program)));
+}
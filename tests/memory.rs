@@ -0,0 +1,80 @@
+mod common;
+
+use common::compile;
+use uniq::{Error, Program, RuntimeErrorKind, State, StateOptions, Value};
+
+fn limited(bytes: usize) -> State {
+    State::with_options(StateOptions::new().with_max_memory(bytes))
+}
+
+fn out_of_memory(program: &Program, state: &mut State) -> uniq::TokenLocation {
+    match state.execute(program) {
+        Err(Error::Runtime(error)) if error.kind == RuntimeErrorKind::OutOfMemory => {
+            assert_eq!(error.location(), program.location(state.program_counter()));
+            error.location().unwrap()
+        }
+        result => panic!("expected to run out of memory, found {result:?}"),
+    }
+}
+
+#[test]
+fn usage_is_tracked() {
+    let mut state = State::new();
+    assert_eq!(state.memory_used(), 0);
+    state
+        .execute(&compile("let x = 9223372036854775807;\nx"))
+        .unwrap();
+    assert!(state.memory_used() > 0);
+    assert!(state.memory_used() <= StateOptions::DEFAULT_MAX_MEMORY);
+}
+
+#[test]
+fn deep_stacks_stop_at_the_limit() {
+    let body = "x + (".repeat(1000) + "x" + &")".repeat(1000);
+    let program = compile(&format!("fn f(x) {{\n  {body}\n}}\nf(1)"));
+    let location = out_of_memory(&program, &mut limited(4096));
+    assert_eq!(location.line, 1);
+    assert_eq!(
+        limited(64 * 1024).execute(&program).unwrap(),
+        Value::Integer(1001)
+    );
+}
+
+#[test]
+fn recursion_stops_at_the_limit() {
+    let program = compile("fn f(x) {\n  f(x + 1)\n}\nf(0)");
+    let mut state = limited(1 << 16);
+    out_of_memory(&program, &mut state);
+    assert!(state.memory_used() <= 1 << 16);
+}
+
+#[test]
+fn boxed_values_count_against_the_limit() {
+    // Every sum is too large for a word, so each level boxes a new integer.
+    let body = "x + (".repeat(300) + "x" + &")".repeat(300);
+    let program = compile(&format!("fn f(x) {{ {body} }}\nf(1125899906842624)"));
+    let stack = program.stack_size().unwrap() * 8;
+    let mut state = limited(stack + 1024);
+    out_of_memory(&program, &mut state);
+    assert!(state.memory_used() > stack);
+    let mut state = limited(stack + 8 * 1024);
+    assert_eq!(
+        state.execute(&program).unwrap(),
+        Value::Integer(301 * 1125899906842624)
+    );
+}
+
+#[test]
+fn crafted_global_slots_do_not_allocate() {
+    let program = Program::assemble(
+        "
+        Integer r0, 1
+        .loc 4:2
+        SetGlobal 4000000000, r0
+        End r0
+        ",
+    )
+    .unwrap();
+    let location = out_of_memory(&program, &mut State::new());
+    assert_eq!((location.line, location.column), (3, 1));
+}
@@ -0,0 +1,98 @@
+use std::{cell::RefCell, io, rc::Rc};
+
+use uniq::{Error, MemoryLoader, ModuleLoader, State, Value};
+
+// Counts how often every module is loaded.
+struct CountingLoader {
+    files: MemoryLoader,
+    loads: Rc<RefCell<Vec<String>>>,
+}
+
+impl ModuleLoader for CountingLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.loads.borrow_mut().push(path.to_string());
+        self.files.load(path)
+    }
+}
+
+fn compile_error(loader: MemoryLoader) -> (String, String) {
+    let mut state = State::with_loader(loader);
+    let Err(Error::Compile(error)) = state.compile_path("main.uq") else {
+        panic!("expected a compile error");
+    };
+    let file = state.sources().get(error.location.file).unwrap();
+    (error.message, file.name().to_string())
+}
+
+#[test]
+fn in_memory_modules_are_imported() {
+    let loader = MemoryLoader::new()
+        .with("lib/math.uq", "fn square(x) { x * x }")
+        .with(
+            "main.uq",
+            "import \"lib/math.uq\" as math;\nmath.square(7) + 1",
+        );
+    let mut state = State::with_loader(loader);
+    let program = state.compile_path("main.uq").unwrap();
+    assert_eq!(state.execute(&program).unwrap(), Value::Integer(50));
+}
+
+#[test]
+fn shared_imports_are_compiled_once() {
+    let files = MemoryLoader::new()
+        .with("math.uq", "fn square(x) { x * x }")
+        .with(
+            "a.uq",
+            "import \"math.uq\" as math;\nfn f(x) { math.square(x) + 1 }",
+        )
+        .with(
+            "b.uq",
+            "import \"math.uq\" as math;\nfn g(x) { math.square(x) - 1 }",
+        )
+        .with(
+            "main.uq",
+            "import \"a.uq\" as a;\nimport \"b.uq\" as b;\na.f(3) * b.g(3)",
+        );
+    let loads = Rc::new(RefCell::new(Vec::new()));
+    let loader = CountingLoader {
+        files,
+        loads: loads.clone(),
+    };
+    let mut state = State::with_loader(loader);
+    let program = state.compile_path("main.uq").unwrap();
+    assert_eq!(state.execute(&program).unwrap(), Value::Integer(80));
+    assert_eq!(*loads.borrow(), ["main.uq", "a.uq", "math.uq", "b.uq"]);
+    let math = state
+        .sources()
+        .files()
+        .filter(|(_, file)| file.name() == "math.uq")
+        .count();
+    assert_eq!(math, 1);
+}
+
+#[test]
+fn import_cycles_are_reported() {
+    let loader = MemoryLoader::new()
+        .with("main.uq", "import \"a.uq\" as a;\na.f()")
+        .with("a.uq", "import \"b.uq\" as b;\nfn f() { 1 }")
+        .with("b.uq", "import \"a.uq\" as a;\nfn g() { 2 }");
+    assert_eq!(
+        compile_error(loader),
+        (
+            "Import cycle detected: a.uq -> b.uq -> a.uq.".to_string(),
+            "b.uq".to_string()
+        )
+    );
+}
+
+#[test]
+fn missing_modules_are_reported() {
+    let loader = MemoryLoader::new().with("main.uq", "import \"missing.uq\" as missing;\n1");
+    assert_eq!(
+        compile_error(loader),
+        (
+            "Unable to load module 'missing.uq': no module named 'missing.uq'.".to_string(),
+            "main.uq".to_string()
+        )
+    );
+}
@@ -0,0 +1,68 @@
+use uniq::{Diagnostic, FileId, TokenLocation, Value};
+
+fn location(offset: u32, line: u32, column: u32) -> Option<TokenLocation> {
+    Some(TokenLocation {
+        file: FileId::default(),
+        offset,
+        line,
+        column,
+        length: 1,
+    })
+}
+
+fn summary(diagnostics: &[Diagnostic]) -> Vec<(&str, &str, Option<TokenLocation>)> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.code,
+                diagnostic.message.as_str(),
+                diagnostic.location,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn independent_errors_are_reported_in_one_pass() {
+    let (ast, diagnostics) =
+        uniq::parse_recovering(b"let a = 1 +;\nlet b = 2;\nlet c = (3 * );\nb * 10");
+    assert_eq!(
+        summary(&diagnostics),
+        [
+            ("E0002", "Expected value, found ';'.", location(11, 0, 11)),
+            ("E0002", "Expected value, found ')'.", location(37, 2, 13)),
+        ]
+    );
+    // The statements between the errors still make up a program.
+    let program = uniq::compile(&ast).unwrap();
+    assert_eq!(uniq::run(&program).unwrap(), Value::Integer(20));
+}
+
+#[test]
+fn lexer_and_parser_errors_are_collected_together() {
+    let (_, diagnostics) = uniq::parse_recovering(b"1 2;\n3 $ 4;\n5");
+    assert_eq!(
+        summary(&diagnostics),
+        [
+            (
+                "E0002",
+                "Expected ';' or end, found '2'.",
+                location(2, 0, 2)
+            ),
+            (
+                "E0001",
+                "Expected ';' or end, found unknown character '$'.",
+                location(7, 1, 2)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn parse_stops_at_the_first_error() {
+    let Err(error) = uniq::parse(b"let a = 1 +;\nlet c = (3 * );") else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(error.location(), location(11, 0, 11));
+}
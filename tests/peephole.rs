@@ -0,0 +1,62 @@
+mod common;
+
+use common::compile_with_level;
+use uniq::{BinaryOperator, Error, Instruction, Value};
+
+const SOURCES: &[&str] = &[
+    "fn f(x) { x * 3 + 1 }\nf(4) - 2",
+    "fn f(x) { x % 7 == 3 }\nf(10)",
+    "fn f(x, y) { x < 2; y >= 1 }\nf(1, 2.5)",
+    "let x = 10;\nlet y = x / 4;\nx - y != 2",
+    "fn f(x) { x; 1; 2.5; x / 2.0 }\nf(9)",
+];
+
+#[test]
+fn fusing_preserves_results() {
+    for source in SOURCES {
+        let fused = uniq::run(&compile_with_level(source, 2)).unwrap();
+        let plain = uniq::run(&compile_with_level(source, 1)).unwrap();
+        assert_eq!(fused, plain, "{source}");
+    }
+}
+
+#[test]
+fn constant_operands_are_fused() {
+    let program = compile_with_level("fn f(x) { x * 3 + 1 }\nf(4)", 2);
+    let body = &program.instructions()[program.function(0).unwrap().entry..];
+    assert_eq!(
+        body,
+        &[
+            Instruction::BinaryConst(BinaryOperator::Multiply, 1, 0, 3),
+            Instruction::BinaryConst(BinaryOperator::Addict, 1, 1, 1),
+            Instruction::Return(1),
+        ]
+    );
+    assert!(program.verify().is_ok());
+    assert_eq!(uniq::run(&program).unwrap(), Value::Integer(13));
+}
+
+#[test]
+fn discarded_values_are_removed() {
+    let program = compile_with_level("let x = 1;\nx", 2);
+    assert!(!program
+        .instructions()
+        .iter()
+        .any(|instruction| matches!(instruction, Instruction::Void(_))));
+}
+
+#[test]
+fn fused_instructions_keep_operator_location() {
+    for source in [
+        "fn f(x) { x / 0 }\nf(1)",
+        "fn f(x) {\n  x\n  +\n  1\n}\nf(true)",
+    ] {
+        let fused = uniq::run(&compile_with_level(source, 2)).unwrap_err();
+        let plain = uniq::run(&compile_with_level(source, 1)).unwrap_err();
+        let (Error::Runtime(fused), Error::Runtime(plain)) = (fused, plain) else {
+            panic!("expected runtime errors");
+        };
+        assert_eq!(fused.kind, plain.kind, "{source}");
+        assert_eq!(fused.location(), plain.location(), "{source}");
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs;
+
+use uniq::{Repl, Value};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("uniq-repl-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn inputs_keep_functions_and_globals() {
+    let mut repl = Repl::new();
+    repl.eval("fn square(x) { x * x }").unwrap();
+    repl.eval("let a = square(3);").unwrap();
+    assert_eq!(repl.eval("square(a) + a").unwrap(), Value::Integer(90));
+}
+
+#[test]
+fn main_code_of_earlier_inputs_is_dropped() {
+    let mut repl = Repl::new();
+    repl.eval("fn square(x) { x * x }").unwrap();
+    for value in 0..50 {
+        repl.eval(&format!("let a = {value};")).unwrap();
+    }
+    // Only the two instructions of `square` come before the new main code.
+    assert_eq!(
+        repl.disassemble("7").unwrap(),
+        "\n@main:  ; main\n    0002  Integer r0, 7\n    0003  End r0\n"
+    );
+    assert_eq!(repl.eval("square(a)").unwrap(), Value::Integer(49 * 49));
+}
+
+#[test]
+fn inputs_without_functions_share_a_source_file() {
+    let mut repl = Repl::new();
+    repl.eval("let a = 1;").unwrap();
+    let files = repl.state().sources().files().count();
+    for _ in 0..20 {
+        repl.eval("a + 1").unwrap();
+    }
+    assert!(repl.eval("a / 0").is_err());
+    assert_eq!(repl.state().sources().files().count(), files);
+
+    // A function keeps its input's source around for its diagnostics.
+    repl.eval("fn half(x) {\n  x / 0\n}").unwrap();
+    repl.eval("a + 2").unwrap();
+    assert_eq!(repl.state().sources().files().count(), files + 1);
+    let error = repl.eval("half(a)").unwrap_err();
+    let location = error.location().unwrap();
+    let file = repl.state().sources().get(location.file).unwrap();
+    assert_eq!(location.line, 1);
+    assert!(file.source().contains("x / 0"));
+}
+
+#[test]
+fn disassembly_lists_only_the_new_code() {
+    let mut repl = Repl::new();
+    repl.eval("fn square(x) { x * x }").unwrap();
+    let listing = repl.disassemble("fn inc(x) { x + 1 }\ninc(2)").unwrap();
+    assert!(!listing.contains("square"), "{listing}");
+    assert!(listing.contains("@f1:  ; fn inc/1"), "{listing}");
+    assert!(listing.contains("Call 1, r0  ; inc"), "{listing}");
+    // Disassembling does not declare anything.
+    assert!(repl.eval("inc(2)").is_err());
+}
+
+#[test]
+fn types_are_inferred_without_running() {
+    let mut repl = Repl::new();
+    repl.eval("fn square(x) { x * x }\nlet a = 2;").unwrap();
+    assert_eq!(repl.type_of("square(a)").unwrap(), "integer");
+    assert_eq!(repl.type_of("square(a) + 1.5").unwrap(), "float");
+    assert_eq!(repl.type_of("square(2.0) < a").unwrap(), "boolean");
+    assert_eq!(repl.type_of("let a = 2.5;").unwrap(), "void");
+    assert_eq!(repl.type_of("let b = 2.5;\nsquare(b)").unwrap(), "float");
+    assert_eq!(repl.type_of("a / 0").unwrap(), "integer");
+    assert_eq!(repl.eval("a").unwrap(), Value::Integer(2));
+    assert!(repl.eval("b").is_err());
+
+    let error = repl.type_of("square(true)").unwrap_err();
+    assert_eq!(error.to_string().lines().count(), 1);
+    assert!(error.location().is_some());
+    repl.eval("fn forever(x) { forever(x) }").unwrap();
+    assert!(repl.type_of("forever(1)").is_err());
+}
+
+#[test]
+fn history_is_loaded_and_extended() {
+    let path = temp_path("history");
+    fs::write(&path, "let a = 1;\na + 1\n").unwrap();
+    let mut repl = Repl::new().with_history(&path);
+    assert_eq!(repl.history(), ["let a = 1;", "a + 1"]);
+
+    let mut out = Vec::new();
+    repl.run("let b = 2;\n:history\n".as_bytes(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("let a = 1;\na + 1\nlet b = 2;\n:history\n"),
+        "{out}"
+    );
+    let saved = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(saved, "let a = 1;\na + 1\nlet b = 2;\n:history\n");
+}
+
+#[test]
+fn multi_line_entries_survive_the_history_file() {
+    let path = temp_path("multi-line");
+    let _ = fs::remove_file(&path);
+    let mut repl = Repl::new().with_history(&path);
+    let mut out = Vec::new();
+    repl.run(
+        "fn f(x) {\n  x + 1\n}\n:load dir\\n.uq\nf(1)\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+    let entries = repl.history().to_vec();
+    let loaded = Repl::new().with_history(&path).history().to_vec();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        entries,
+        ["fn f(x) {\n  x + 1\n}", ":load dir\\n.uq", "f(1)"]
+    );
+    assert_eq!(loaded, entries);
+}
+
+#[test]
+fn commands_leave_unfinished_input() {
+    let mut repl = Repl::new();
+    let mut out = Vec::new();
+    repl.run("fn f(x) {\n:quit\n1 / 0\n".as_bytes(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out, "-> .. ");
+    assert_eq!(repl.history(), [":quit"]);
+
+    let mut out = Vec::new();
+    repl.run("(1 +\n:type 2\n3\n".as_bytes(), &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "-> .. integer\n-> 3\n-> \n"
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::compile;
+use uniq::{Error, Program, RuntimeErrorKind, State, StateOptions, Value};
+
+// fn f(x) { x + (x + (x + ... x)) }, every level keeps one more value alive.
+fn chain(depth: usize) -> String {
+    let body = "x + (".repeat(depth) + "x" + &")".repeat(depth);
+    format!("fn f(x) {{ {body} }}\nf(1)")
+}
+
+fn overflow(result: uniq::Result<Value>) -> bool {
+    matches!(result, Err(Error::Runtime(error)) if error.kind == RuntimeErrorKind::StackOverflow)
+}
+
+#[test]
+fn deep_expressions_grow_the_stack() {
+    let program = compile(&chain(1000));
+    assert!(program.stack_size().unwrap() > 1000);
+    assert_eq!(
+        State::new().execute(&program).unwrap(),
+        Value::Integer(1001)
+    );
+}
+
+#[test]
+fn stack_size_covers_the_call_graph() {
+    let program = compile("fn g(a, b) { a * b }\nfn f(x) { 1 + g(x, x + 1) }\n2 + f(3)");
+    assert_eq!(program.stack_size(), Some(6));
+    assert_eq!(compile("1 + 2").stack_size(), Some(1));
+    assert_eq!(compile("fn f(x) { f(x + 1) }\nf(0)").stack_size(), None);
+}
+
+#[test]
+fn stack_size_is_recomputed_on_load() {
+    let program = compile(&chain(10));
+    let bytes = program.to_bytes(uniq::Encoding::Binary).unwrap();
+    let loaded = Program::from_bytes(&bytes, uniq::Encoding::Binary).unwrap();
+    assert_eq!(loaded.stack_size(), program.stack_size());
+}
+
+#[test]
+fn the_limit_is_configurable() {
+    let program = compile(&chain(1000));
+    let mut state = State::with_options(StateOptions::new().with_max_stack(500));
+    assert!(overflow(state.execute(&program)));
+    state.set_options(StateOptions::new().with_max_stack(2000));
+    assert_eq!(state.execute(&program).unwrap(), Value::Integer(1001));
+}
+
+#[test]
+fn unbounded_recursion_overflows() {
+    let program = compile("fn f(x) { f(x + 1) }\nf(0)");
+    let mut state = State::with_options(StateOptions::new().with_max_stack(10_000));
+    assert!(overflow(state.execute(&program)));
+    let program = compile("fn f() { f() }\nf()");
+    assert!(overflow(state.execute(&program)));
+}
+
+#[test]
+fn calls_without_arguments_at_the_stack_edge() {
+    // Deserializing skips verification and measuring, so nothing is reserved ahead of the call
+    // and `f` returns a register it never wrote.
+    let program: Program = serde_json::from_str(
+        r#"{"version":"0.1.0","entry":0,
+            "instructions":[{"Call":[0,256]},{"End":256},{"Return":0}],
+            "locations":[],"parents":{},"functions":[{"name":"f","arity":0,"entry":2}],"files":[]}"#,
+    )
+    .unwrap();
+    assert_eq!(uniq::run(&program).unwrap(), Value::Void);
+    let mut state = State::with_options(StateOptions::new().with_max_stack(256));
+    assert!(overflow(state.execute(&program)));
+    assert!(Program::assemble(&program.disassemble(None)).is_err());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::compile;
+use uniq::{Error, RuntimeErrorKind, State, StepResult, Value};
+
+const SOURCE: &str = "fn add(x, y) {\n  x + y\n}\nlet a = add(2, 3);\nlet b = add(a, 4);\na * b\n";
+
+#[test]
+fn stepping_matches_a_full_run() {
+    let program = compile(SOURCE);
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    let mut steps = 0;
+    let result = loop {
+        steps += 1;
+        match state.step(&program) {
+            StepResult::Running => {}
+            StepResult::Finished(value) => break value,
+            result => panic!("unexpected {result:?}"),
+        }
+    };
+    assert!(steps > 1);
+    assert_eq!(result, uniq::run(&program).unwrap());
+    assert_eq!(result, Value::Integer(45));
+}
+
+#[test]
+fn stepping_past_the_end_keeps_the_result() {
+    let program = compile("1 + 2");
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    while let StepResult::Running = state.step(&program) {}
+    for _ in 0..3 {
+        assert!(matches!(
+            state.step(&program),
+            StepResult::Finished(Value::Integer(3))
+        ));
+    }
+}
+
+// Lines in locations count from zero.
+#[test]
+fn run_until_pauses_in_front_of_the_chosen_instruction() {
+    let program = compile(SOURCE);
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    let result = state.run_until(&program, |state| {
+        state
+            .location(&program)
+            .is_some_and(|location| location.line == 1)
+    });
+    assert!(matches!(result, StepResult::Paused));
+    assert_eq!(state.location(&program).unwrap().line, 1);
+    assert_eq!(state.frames().len(), 1);
+
+    // The first call's arguments sit at the frame base, the frame ends at the stack pointer.
+    let base = state.frame_base();
+    assert_eq!(state.stack_pointer(), base + program.frame_size(Some(0)));
+    let stack = state.stack();
+    assert_eq!(stack.len(), state.stack_pointer());
+    assert_eq!(
+        stack[base..base + 2],
+        [Value::Integer(2), Value::Integer(3)]
+    );
+
+    // The predicate is not checked before the first instruction, so this reaches the second call.
+    let entry = program.function(0).unwrap().entry;
+    let result = state.run_until(&program, |state| state.program_counter() == entry);
+    assert!(matches!(result, StepResult::Paused));
+    let base = state.frame_base();
+    assert_eq!(
+        state.stack()[base..base + 2],
+        [Value::Integer(5), Value::Integer(4)]
+    );
+
+    assert_eq!(state.resume(&program).unwrap(), Value::Integer(45));
+}
+
+#[test]
+fn run_until_finishes_when_nothing_matches() {
+    let program = compile(SOURCE);
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    assert!(matches!(
+        state.run_until(&program, |_| false),
+        StepResult::Finished(Value::Integer(45))
+    ));
+}
+
+#[test]
+fn the_main_frame_is_visible_before_the_first_step() {
+    let program = compile(SOURCE);
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    assert_eq!(state.program_counter(), program.entry());
+    assert_eq!(state.frame_base(), 0);
+    assert_eq!(state.stack_pointer(), program.frame_size(None));
+    assert!(state.stack().iter().all(|value| *value == Value::Void));
+}
+
+#[test]
+fn failing_steps_carry_the_location() {
+    let program = compile("let x = 1;\nx / 0\n");
+    let mut state = State::new();
+    state.start(&program).unwrap();
+    let error = loop {
+        match state.step(&program) {
+            StepResult::Running => {}
+            StepResult::Failed(error) => break error,
+            result => panic!("unexpected {result:?}"),
+        }
+    };
+    let Error::Runtime(error) = error else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+    assert_eq!(error.location().unwrap().line, 1);
+}
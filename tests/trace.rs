@@ -0,0 +1,76 @@
+use uniq::{Error, MemoryLoader, RuntimeError, RuntimeErrorKind, State};
+
+fn runtime_error(loader: MemoryLoader) -> (State, RuntimeError) {
+    let mut state = State::with_loader(loader);
+    let program = state.compile_path("main.uq").unwrap();
+    let Err(Error::Runtime(error)) = state.execute(&program) else {
+        panic!("expected a runtime error");
+    };
+    (state, *error)
+}
+
+// The spans of a trace in `source`, with lines counting from zero.
+fn spans<'a>(error: &RuntimeError, source: &'a str) -> Vec<(u32, &'a str)> {
+    error
+        .trace
+        .iter()
+        .map(|location| {
+            let begin = location.offset as usize;
+            (
+                location.line,
+                &source[begin..begin + location.length as usize],
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn traces_run_from_the_failing_instruction_out_to_the_script() {
+    let source = "\
+fn inner(x) {
+  x / 0
+}
+fn outer(x) {
+  inner(x) + 1
+}
+let a = outer(2) * 3;
+a
+";
+    let (_, error) = runtime_error(MemoryLoader::new().with("main.uq", source));
+    assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+    // Each frame lists its call, then the expressions that enclose it.
+    assert_eq!(
+        spans(&error, source),
+        [
+            (1, "/"),
+            (4, "inner"),
+            (4, "+"),
+            (6, "outer"),
+            (6, "*"),
+            (6, "let"),
+        ]
+    );
+    assert_eq!(error.location(), error.trace.first().copied());
+}
+
+#[test]
+fn locations_in_imported_files_resolve_to_that_file() {
+    let math = "fn square(x) { x * x }\nfn half(x) {\n  x / 0\n}\n";
+    let main = "import \"math.uq\" as math;\nmath.half(math.square(3))\n";
+    let loader = MemoryLoader::new()
+        .with("math.uq", math)
+        .with("main.uq", main);
+    let (state, error) = runtime_error(loader);
+
+    let location = error.location().unwrap();
+    let file = state.sources().get(location.file).unwrap();
+    assert_eq!(file.name(), "math.uq");
+    assert_eq!(location.offset as usize, math.find("/ 0").unwrap());
+    assert_eq!((location.line, location.column), (2, 4));
+
+    let call = error.trace[1];
+    let file = state.sources().get(call.file).unwrap();
+    assert_eq!(file.name(), "main.uq");
+    assert_eq!(call.offset as usize, main.find("half").unwrap());
+    assert_ne!(call.file, location.file);
+}
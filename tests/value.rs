@@ -0,0 +1,76 @@
+mod common;
+
+use common::call_tree;
+use uniq::{Repl, Value};
+
+fn run(source: &str) -> Value {
+    uniq::run(&uniq::parse_and_compile(source.as_bytes()).unwrap()).unwrap()
+}
+
+#[test]
+fn values_survive_the_stack() {
+    assert_eq!(run("1 < 2"), Value::Boolean(true));
+    assert_eq!(run("fn f(x) { x }\nf(false)"), Value::Boolean(false));
+    let negative_zero = run("fn f(x) { x * (0.0 - 1.0) }\nf(0.0)");
+    assert!(matches!(negative_zero, Value::Float(x) if x == 0.0 && x.is_sign_negative()));
+    assert_eq!(run("fn f(x) { x * 0.5 }\nf(3)"), Value::Float(1.5));
+    assert!(matches!(run("fn f(x) { x / 0.0 }\nf(0.0)"), Value::Float(x) if x.is_nan()));
+    let infinity = run("fn f(x) { x / 0.0 }\nf(1.0)");
+    assert_eq!(infinity, Value::Float(f64::INFINITY));
+}
+
+#[test]
+fn integers_keep_all_64_bits() {
+    let cases = [
+        ("fn f(x) { x }\nf(140737488355327)", 140737488355327),
+        ("fn f(x) { x + 1 }\nf(140737488355327)", 140737488355328),
+        (
+            "fn f(x) { x - 1 }\nf(0 - 140737488355328)",
+            -140737488355329,
+        ),
+        ("fn f(x) { x * x }\nf(4294967296)", 0),
+        ("fn f(x) { x + 1 }\nf(9223372036854775807)", i64::MIN),
+        (
+            "fn f(x, y) { x / y }\nf(9223372036854775807, 3)",
+            i64::MAX / 3,
+        ),
+        (
+            "fn f(x) { x % 1000000007 }\nf(9223372036854775807)",
+            i64::MAX % 1000000007,
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(run(source), Value::Integer(expected), "{source}");
+    }
+}
+
+#[test]
+fn large_integers_survive_collection() {
+    // Builds enough boxed intermediates to force several collections of the integer heap.
+    let source = call_tree(12, "(x + 281474976710656) * 1099511627776 + 1");
+    let mut expected = |x: i64| {
+        x.wrapping_add(281474976710656)
+            .wrapping_mul(1099511627776)
+            .wrapping_add(1)
+    };
+    fn tree(level: u32, x: i64, leaf: &mut dyn FnMut(i64) -> i64) -> i64 {
+        if level == 0 {
+            return leaf(x);
+        }
+        tree(level - 1, x + 1, leaf).wrapping_add(tree(level - 1, x * 2, leaf))
+    }
+    let expected = tree(12, 1, &mut expected);
+    assert_eq!(run(&source), Value::Integer(expected));
+}
+
+#[test]
+fn globals_keep_large_integers_between_runs() {
+    let mut repl = Repl::new();
+    repl.eval("let big = 9223372036854775000;").unwrap();
+    repl.eval("let other = big + 7;").unwrap();
+    assert_eq!(repl.eval("other - big").unwrap(), Value::Integer(7));
+    assert_eq!(
+        repl.eval("big").unwrap(),
+        Value::Integer(9223372036854775000)
+    );
+}
@@ -0,0 +1,150 @@
+use uniq::{Error, MemoryLoader, Program, State, StateOptions};
+
+fn verify(text: &str) -> Result<(), (String, Option<usize>)> {
+    verify_with_stack(text, StateOptions::DEFAULT_MAX_STACK)
+}
+
+fn verify_with_stack(text: &str, max_stack: usize) -> Result<(), (String, Option<usize>)> {
+    // Assembling verifies against the default limit already.
+    match Program::assemble(text).and_then(|program| program.verify_with_stack(max_stack)) {
+        Ok(()) => Ok(()),
+        Err(Error::Verify(error)) => Err((error.message, error.index)),
+        Err(error) => panic!("unexpected error: {error}"),
+    }
+}
+
+#[test]
+fn compiled_programs_are_valid() {
+    let loader = MemoryLoader::new()
+        .with("math.uq", "fn square(x) { x * x }")
+        .with(
+            "main.uq",
+            "import \"math.uq\" as math;\nfn f(a, b) { math.square(a) - b }\nlet x = f(3, 1);\nx / 2",
+        );
+    let program = State::with_loader(loader).compile_path("main.uq").unwrap();
+    program.verify().unwrap();
+}
+
+#[test]
+fn unwritten_registers_are_rejected() {
+    assert_eq!(
+        verify("Integer r0, 1\nAddict r0, r0, r1\nEnd r0"),
+        Err((
+            "'Addict r0, r0, r1' reads r1 before it is written".to_string(),
+            Some(1)
+        ))
+    );
+}
+
+#[test]
+fn missing_end_is_rejected() {
+    assert_eq!(
+        verify("Integer r0, 1\nVoid r0"),
+        Err((
+            "Code starting at 0 runs past the end of the program".to_string(),
+            Some(2)
+        ))
+    );
+}
+
+#[test]
+fn stack_limit_is_enforced() {
+    assert_eq!(verify("Void r256\nEnd r256"), Ok(()));
+    assert_eq!(
+        verify_with_stack("Void r256\nEnd r256", 256),
+        Err((
+            "Register r256 exceeds the stack size of 256".to_string(),
+            Some(0)
+        ))
+    );
+    // The result of a call without arguments needs a slot too.
+    let call =
+        ".function f 0 f\n.entry main\nf:\nVoid r0\nReturn r0\nmain:\nCall f, r256\nEnd r256";
+    assert_eq!(verify(call), Ok(()));
+    assert_eq!(
+        verify_with_stack(call, 256),
+        Err((
+            "Call at r256 exceeds the stack size of 256".to_string(),
+            Some(2)
+        ))
+    );
+}
+
+#[test]
+fn functions_are_checked() {
+    assert_eq!(
+        verify("Call 0, r0\nEnd r0"),
+        Err(("Call to unknown function 0".to_string(), Some(0)))
+    );
+    assert_eq!(
+        verify(".function f 1 f\n.entry main\nf:\nReturn r1\nmain:\nVoid r0\nCall f, r0\nEnd r0"),
+        Err((
+            "'Return r1' reads r1 before it is written".to_string(),
+            Some(0)
+        ))
+    );
+    assert_eq!(
+        verify(".function f 0 f\n.entry main\nf:\nVoid r0\nEnd r0\nmain:\nCall f, r0\nEnd r0"),
+        Err((
+            "Function 'f' reaches End instead of Return".to_string(),
+            Some(1)
+        ))
+    );
+    assert_eq!(
+        verify("Void r0\nReturn r0"),
+        Err(("Return outside of a function".to_string(), Some(1)))
+    );
+}
+
+#[test]
+fn regions_do_not_share_code() {
+    assert_eq!(
+        verify(
+            ".function f 1 f\n.function g 1 f\n.entry main\nf:\nReturn r0\nmain:\nVoid r0\nEnd r0"
+        ),
+        Err(("'f' and 'g' both start at 0".to_string(), Some(0)))
+    );
+    assert_eq!(
+        verify(".function f 1 f\n.function g 1 g\n.entry main\nf:\nMove r1, r0\ng:\nReturn r0\nmain:\nVoid r0\nEnd r0"),
+        Err(("'g' starts at 1 inside the code of 'f'".to_string(), Some(1)))
+    );
+}
+
+#[test]
+fn assembling_rejects_invalid_programs() {
+    assert!(matches!(
+        Program::assemble("Addict r0, r0, r0\nEnd r0"),
+        Err(Error::Verify(_))
+    ));
+}
+
+#[test]
+fn locations_are_checked() {
+    assert_eq!(
+        verify(".file \"main.uq\"\n.loc 1:1 file=1\nVoid r0\nEnd r0"),
+        Err(("Location refers to unknown file 1".to_string(), Some(0)))
+    );
+    assert_eq!(
+        verify(".parent 9\nVoid r0\nEnd r0"),
+        Err((
+            "Parent link 0 -> 9 points outside of the program".to_string(),
+            Some(0)
+        ))
+    );
+}
+
+#[test]
+fn loading_rejects_invalid_programs() {
+    let path = std::env::temp_dir().join("uniq-verifier-test.uqb");
+    let path = path.to_str().unwrap();
+    // Deserializing does not verify, so this writes code the assembler would reject.
+    let program: Program = serde_json::from_str(
+        r#"{"version":"0.1.0","entry":0,"instructions":[{"Binary":["Addict",0,0,0]},{"End":0}],
+            "locations":[],"parents":{},"functions":[],"files":[]}"#,
+    )
+    .unwrap();
+    program.save_bin(path).unwrap();
+    let result = Program::load_bin(path);
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(result, Err(Error::Verify(_))));
+}